@@ -1,4 +1,6 @@
 use crate::mana::*;
+use crate::zone::ZoneType;
+use crate::expr::Expr;
 use enumflags2::{bitflags, BitFlags};
 
 #[bitflags]
@@ -14,6 +16,25 @@ pub enum Types {
     Instant = 0x40,
 }
 
+#[bitflags]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Supertype {
+    Basic = 0x01,
+    Legendary = 0x02,
+    Snow = 0x04,
+    World = 0x08,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CounterType {
+    PlusOne,                        // +1/+1
+    MinusOne,                       // -1/-1
+    Loyalty,
+    Charge,
+    Oil,
+}
+
 /*
 
 {
@@ -107,15 +128,136 @@ pub enum Types {
 pub enum Effect {
     ProduceMana(ManaPool),
     FetchLand { to_hand: Vec<String>, to_battlefield: Vec<String> }, // like 'Cultivate'
-    LandLimit(u32), // the increase in playable lands
+    LandLimit(u32), // the increase in playable lands, this turn when cast, each turn when static
     Draw(Vec<u32>),                 // like 'Harmonize' or 'Read the Bones'
+    Tutor { filter: CardFilter, destination: Destination, preferences: Vec<TutorPreference> }, // like 'Demonic Tutor'
+    Scry(u32),
+    Surveil(u32),
+    Dig { count: u32, filter: CardFilter }, // look at the top cards, take one, like 'Impulse'
+    NoMaximumHandSize,              // like 'Reliquary Tower'
+    ReturnFromGraveyard { filter: CardFilter, destination: Destination, count: u32 }, // like 'Eternal Witness'
+    PlayLandsFromGraveyard,         // like 'Crucible of Worlds'
+    CastFromGraveyard { exile: u32 }, // for the mana cost, exiling other cards from the graveyard, like flashback and escape
+    PlayLandsFromLibraryTop,        // like 'Oracle of Mul Daya' or 'Courser of Kruphix'
+    CostReduction { filter: CardFilter, amount: Amount, floor: u32 }, // generic mana, not below 'floor' in total, like 'Goblin Electromancer'
+    CostIncrease { filter: CardFilter, count: u32 }, // like 'Sphere of Resistance'
+    Cascade,                        // like 'Bloodbraid Elf'
+    Discover(u32),                  // free cast with mana value up to N, like 'Trumpeting Carnosaur'
+    ProduceManaPer { mana: ManaPool, amount: Amount }, // like 'Gaea's Cradle' or 'Cabal Coffers'
+    ManaMultiplier { filter: CardFilter, factor: u32 }, // like 'Mana Reflection' or 'Zendikar Resurgent'
+    UntapLands(u32),                // like 'Garruk Wildspeaker'
+    CreateToken { token: Box<CardData>, count: u32 },
+    DrawPer(Amount),                // like 'Armorcraft Judge'
+    AddCounters { counter: CounterType, count: u32, filter: Option<CardFilter> }, // on the card itself without a filter
+    MoveCounters { counter: CounterType, count: u32, filter: CardFilter }, // like 'Nesting Grounds'
+    Proliferate,
+    PerOpponent { effect: Box<Effect>, chance: f32 }, // repeated for each opponent taking the offer, like 'Tempt with Discovery'
+}
+
+// A number computed from the board.
+#[derive(Debug, PartialEq)]
+pub enum Amount {
+    Count(CardFilter),              // permanents we control matching the filter
+    Devotion(Option<Color>),        // devotion to a color, or to our best color
+    Counters(CounterType, CardFilter), // counters on the permanents matching the filter
+    WithCounters(CounterType, CardFilter), // permanents matching the filter with such counters
+    Expr(Expr),                     // like "count(battlefield, creature) / 2"
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Destination {
+    Hand,
+    LibraryTop,
+    Battlefield,
+}
+
+// What a tutor should look for, in order of preference.
+#[derive(Debug, PartialEq)]
+pub enum TutorPreference {
+    Matching(CardFilter),           // a specific card, tag, etc..
+    MissingColorSource,             // a card producing a color we need
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Trigger {
     Cast,
     Activated,
-    Upkeep
+    Static,                         // applies while on the battlefield
+    BeginningOf(Phase),             // like 'Phyrexian Arena' or 'Howling Mine'
+    SpellCast(CardFilter),          // like 'Beast Whisperer' or 'The Great Henge'
+    ZoneChange { from: Option<ZoneType>, to: Option<ZoneType>, filter: CardFilter }, // like landfall or dies triggers
+    EachDrawStep,                   // every player's draw step, like 'Howling Mine'
+    Opponent(TableEvent),           // like 'Smothering Tithe' or 'Rhystic Study'
+}
+
+// What the opponents do on their turns, see Table in game.rs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableEvent {
+    Draw,
+    SpellCast,
+    SecondSpellCast,                // their second spell each turn, like 'Monologue Tax'
+    CreatureDies,
+}
+
+pub fn parse_table_event(text : &str) -> Option<TableEvent> {
+    match text {
+        "opponent-draws" => Some(TableEvent::Draw),
+        "opponent-spell-cast" => Some(TableEvent::SpellCast),
+        "opponent-second-spell" => Some(TableEvent::SecondSpellCast),
+        "opponent-creature-dies" => Some(TableEvent::CreatureDies),
+        _ => None
+    }
+}
+
+// The steps of our turn, see Turn::play() in game.rs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Untap,
+    Upkeep,
+    Draw,
+    FirstMain,
+    Combat,
+    SecondMain,
+    End,
+    Cleanup
+}
+
+pub const PHASES : [Phase; 8] = [
+    Phase::Untap,
+    Phase::Upkeep,
+    Phase::Draw,
+    Phase::FirstMain,
+    Phase::Combat,
+    Phase::SecondMain,
+    Phase::End,
+    Phase::Cleanup
+];
+
+pub fn parse_phase(text : &str) -> Option<Phase> {
+    match text {
+        "untap" => Some(Phase::Untap),
+        "upkeep" => Some(Phase::Upkeep),
+        "draw-step" => Some(Phase::Draw),
+        "main-1" => Some(Phase::FirstMain),
+        "combat" => Some(Phase::Combat),
+        "main-2" => Some(Phase::SecondMain),
+        "end-step" => Some(Phase::End),
+        "cleanup" => Some(Phase::Cleanup),
+        _ => None
+    }
+}
+
+// Selects cards based on their types, an empty 'types' matches any card.
+// The optional properties narrow it down further.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CardFilter {
+    pub types: BitFlags<Types>,
+    pub excluded_types: BitFlags<Types>,
+    pub name: Option<String>,
+    pub tag: Option<String>,
+    pub subtype: Option<String>,    // type words, like "dragon" or "basic swamp"
+    pub min_cmc: Option<u32>,
+    pub max_cmc: Option<u32>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -127,14 +269,38 @@ pub enum Cost {
     TapSacrifice,
     TapMana(ManaPool),
     TapManaSacrifice(ManaPool),
+    Life(u32),                      // like 'Necropotence'
+    TapLife(u32),                   // like 'Horizon Canopy'
+    ManaLife(ManaPool, u32),
+    TapSacrificeLife(u32),          // fetch lands like 'Flooded Strand'
+    Loyalty(i32),                   // planeswalker +N/-N abilities
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Ability {
     pub trigger: Trigger,
     pub cost: Cost,
     pub effect: Effect,
     pub availability: f32,
+    // Where the ability can be used from. Abilities used from hand, like
+    // cycling and channel, discard the card as part of their cost. Eminence
+    // works both on the battlefield and in the command zone.
+    pub zones: BitFlags<ZoneType>,
+    // The ability is only used while this holds, like "hand_size <= 3".
+    pub condition: Option<Expr>,
+}
+
+// When a permanent, typically a land, enters the battlefield tapped.
+#[derive(Debug, PartialEq)]
+pub enum EntersTapped {
+    Never,
+    Always,
+    UnlessControl(Vec<String>),     // a land of one of the types, check lands like 'Sulfur Falls'
+    UnlessAtMostOtherLands(u32),    // fast lands like 'Blackcleave Cliffs'
+    UnlessAtLeastOtherLands(u32),   // slow lands like 'Shipwreck Marsh'
+    UnlessAtLeastBasicLands(u32),   // battle lands like 'Prairie Stream'
+    UnlessPayLife(u32),             // shock lands like 'Overgrown Tomb'
+    UnlessOpponents(u32),           // bond lands like 'Bountiful Promenade'
 }
 
 #[derive(Debug, PartialEq)]
@@ -142,53 +308,151 @@ pub enum AdditionalCost {
     ReturnLandToHand,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct CardData {
     pub name: String,
     pub cmc: u32,
     pub mana_cost: Option<ManaPool>,
     pub type_string: String,
+    pub supertypes: BitFlags<Supertype>,
     pub types: BitFlags<Types>,
+    pub subtypes: Vec<String>,      // lower cased, like "forest" or "dragon"
 
     pub produced_mana: Option<Mana>,
-    pub enters_tapped: bool,
+    pub enters_tapped: EntersTapped,
+    pub haste: bool,
+    pub loyalty: Option<u32>,       // starting loyalty of planeswalkers
 
     pub abilities: Option<Vec<Ability>>,
     pub additional_cost: Option<AdditionalCost>,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Card<'db> {
     pub id: u32,
     pub data: &'db CardData,
-    pub tapped: bool
+    pub tapped: bool,
+    pub entered_battlefield: u32,   // turn number, 0 when it was there before the game started
+    pub zone: ZoneType,             // the zone the card is in, or was taken from
+    pub counters: std::collections::BTreeMap<CounterType, u32>,
+    pub commander: bool,            // goes back to the command zone rather than the graveyard or exile
 }
 
-pub fn parse_types(types : &str) -> BitFlags<Types, u8> {
-    let lower_cased = types.to_lowercase();
-    let mut flags = BitFlags::empty();
-    if lower_cased.find("land").is_some() {
-        flags |= Types::Land;
+// Splits a type line, like "Legendary Creature — Elf Druid", into its
+// supertypes, card types and subtypes. Both faces count for double faced
+// cards, like "Sorcery // Land".
+pub fn parse_type_line(type_line : &str) -> (BitFlags<Supertype>, BitFlags<Types>, Vec<String>) {
+    let mut supertypes = BitFlags::empty();
+    let mut types = BitFlags::empty();
+    let mut subtypes : Vec<String> = Vec::new();
+    for face in type_line.split("//") {
+        let (left, right) = face.split_once('—').unwrap_or((face, ""));
+        for word in left.split_whitespace().map(|w| w.to_lowercase()) {
+            if let Some(supertype) = parse_supertype(&word) {
+                supertypes |= supertype;
+            } else if let Some(t) = parse_type(&word) {
+                types |= t;
+            }
+        }
+        for word in right.split_whitespace().map(|w| w.to_lowercase()) {
+            if !subtypes.contains(&word) {
+                subtypes.push(word);
+            }
+        }
     }
-    if lower_cased.find("creature").is_some() {
-        flags |= Types::Creature;
+    return (supertypes, types, subtypes);
+}
+
+fn parse_supertype(name : &str) -> Option<Supertype> {
+    match name {
+        "basic" => Some(Supertype::Basic),
+        "legendary" => Some(Supertype::Legendary),
+        "snow" => Some(Supertype::Snow),
+        "world" => Some(Supertype::World),
+        _ => None
     }
-    if lower_cased.find("planeswalker").is_some() {
-        flags |= Types::Planeswalker;
+}
+
+fn parse_type(name : &str) -> Option<Types> {
+    match name {
+        "land" => Some(Types::Land),
+        "creature" => Some(Types::Creature),
+        "planeswalker" => Some(Types::Planeswalker),
+        "artifact" => Some(Types::Artifact),
+        "enchantment" => Some(Types::Enchantment),
+        "sorcery" => Some(Types::Sorcery),
+        "instant" => Some(Types::Instant),
+        _ => None
     }
-    if lower_cased.find("artifact").is_some() {
-        flags |= Types::Artifact;
+}
+
+pub fn parse_counter_type(name : &str) -> Option<CounterType> {
+    match name {
+        "+1/+1" => Some(CounterType::PlusOne),
+        "-1/-1" => Some(CounterType::MinusOne),
+        "loyalty" => Some(CounterType::Loyalty),
+        "charge" => Some(CounterType::Charge),
+        "oil" => Some(CounterType::Oil),
+        _ => None
     }
-    if lower_cased.find("enchantment").is_some() {
-        flags |= Types::Enchantment;
+}
+
+// Parses filters like "creature", "noncreature" or "instant/sorcery".
+pub fn parse_card_filter(text : &str) -> Result<CardFilter, String> {
+    let mut filter = CardFilter::any();
+    for part in text.to_lowercase().split('/').map(|p| p.trim()) {
+        if part == "any" || part == "spell" {
+            continue;
+        } else if let Some(t) = part.strip_prefix("non").and_then(parse_type) {
+            filter.excluded_types |= t;
+        } else if let Some(t) = parse_type(part) {
+            filter.types |= t;
+        } else {
+            return Err(format!("invalid card filter '{}'", text));
+        }
     }
-    if lower_cased.find("sorcery").is_some() {
-        flags |= Types::Sorcery;
+    return Ok(filter);
+}
+
+impl CardFilter {
+    pub fn any() -> Self {
+        return CardFilter {
+            types: BitFlags::empty(),
+            excluded_types: BitFlags::empty(),
+            name: None,
+            tag: None,
+            subtype: None,
+            min_cmc: None,
+            max_cmc: None
+        };
     }
-    if lower_cased.find("instant").is_some() {
-        flags |= Types::Instant;
+
+    pub fn matches(&self, data : &CardData) -> bool {
+        if !self.types.is_empty() && !data.types.intersects(self.types) {
+            return false;
+        }
+        if data.types.intersects(self.excluded_types) {
+            return false;
+        }
+        if let Some(name) = &self.name {
+            if !data.name.eq_ignore_ascii_case(name) {
+                return false;
+            }
+        }
+        if let Some(tag) = &self.tag {
+            if !data.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                return false;
+            }
+        }
+        if let Some(subtype) = &self.subtype {
+            if !data.matches_type_query(subtype) {
+                return false;
+            }
+        }
+        return self.min_cmc.map_or(true, |min| data.cmc >= min)
+            && self.max_cmc.map_or(true, |max| data.cmc <= max);
     }
-    return flags;
 }
 
 impl<'db> Card<'db> {
@@ -197,6 +461,10 @@ impl<'db> Card<'db> {
             id: 0,
             data: data,
             tapped: false,
+            entered_battlefield: 0,
+            zone: ZoneType::Library,
+            counters: std::collections::BTreeMap::new(),
+            commander: false,
         };
         return card;
     }
@@ -212,10 +480,55 @@ impl<'db> Card<'db> {
         return self.data.types.contains(t);
     }
 
+    pub fn counters(&self, counter : CounterType) -> u32 {
+        return *self.counters.get(&counter).unwrap_or(&0);
+    }
+
+    // +1/+1 and -1/-1 counters cancel each other out.
+    pub fn add_counters(&mut self, counter : CounterType, count : u32) {
+        let opposite = match counter {
+            CounterType::PlusOne => Some(CounterType::MinusOne),
+            CounterType::MinusOne => Some(CounterType::PlusOne),
+            _ => None
+        };
+        let mut count = count;
+        if let Some(opposite) = opposite {
+            count -= self.remove_counters(opposite, count);
+        }
+        if count > 0 {
+            *self.counters.entry(counter).or_insert(0) += count;
+        }
+    }
+
+    // Returns how many were actually removed.
+    pub fn remove_counters(&mut self, counter : CounterType, count : u32) -> u32 {
+        let present = self.counters(counter);
+        let removed = present.min(count);
+        if removed == present {
+            self.counters.remove(&counter);
+        } else {
+            self.counters.insert(counter, present - removed);
+        }
+        return removed;
+    }
+
+    // Tokens cease to exist when they leave the battlefield.
+    pub fn is_token(&self) -> bool {
+        return self.data.type_string.starts_with("Token");
+    }
+
+    // Creatures can't use tap abilities the turn they arrive, unless they have haste.
+    pub fn is_summoning_sick(&self, turn_number : u32) -> bool {
+        return self.is_type(Types::Creature)
+            && !self.data.haste
+            && self.entered_battlefield == turn_number;
+    }
+
+    // The mana from tapping the card, mana converters aside.
     pub fn produced_mana(&self) -> Option<ManaPool> {
         for ability in self.data.abilities.iter().flatten() {
             match &ability.effect {
-                Effect::ProduceMana(mana) => return Some(mana.clone()),
+                Effect::ProduceMana(mana) if !ability.is_mana_converter() => return Some(mana.clone()),
                 _ => ()
             }
         }
@@ -229,6 +542,9 @@ impl<'db> std::fmt::Display for Card<'db> {
         if self.tapped {
             write!(f, " *TAPPED*")?;
         }
+        for (counter, count) in &self.counters {
+            write!(f, " {}x{}", count, counter)?;
+        }
         if self.data.mana_cost.is_some() {
             write!(f, " - {} ({})", self.data.mana_cost.as_ref().unwrap(), self.data.cmc)?;
         }
@@ -262,6 +578,11 @@ impl std::fmt::Display for Cost {
             Cost::TapSacrifice => write!(f, "tap,sac"),
             Cost::TapMana(pool) => write!(f, "tap,{}", pool),
             Cost::TapManaSacrifice(pool) => write!(f, "tap,sac,{}", pool),
+            Cost::Life(life) => write!(f, "life={}", life),
+            Cost::TapLife(life) => write!(f, "tap,life={}", life),
+            Cost::ManaLife(pool, life) => write!(f, "{},life={}", pool, life),
+            Cost::TapSacrificeLife(life) => write!(f, "tap,sac,life={}", life),
+            Cost::Loyalty(loyalty) => write!(f, "loyalty={:+}", loyalty),
         }
     }
 }
@@ -271,25 +592,158 @@ impl std::fmt::Display for Trigger {
         match self {
             Trigger::Cast => write!(f, "cast"),
             Trigger::Activated => write!(f, "activated"),
-            Trigger::Upkeep => write!(f, "upkeep")
+            Trigger::Static => write!(f, "static"),
+            Trigger::BeginningOf(phase) => write!(f, "{}", phase),
+            Trigger::SpellCast(filter) => write!(f, "spell-cast({})", filter),
+            Trigger::ZoneChange { from, to, filter } => write!(f, "zone-change({}->{}, {})",
+                from.map_or("any".to_string(), |zone| zone.to_string()),
+                to.map_or("any".to_string(), |zone| zone.to_string()),
+                filter),
+            Trigger::EachDrawStep => write!(f, "each-draw-step"),
+            Trigger::Opponent(event) => write!(f, "{}", event)
         }
     }
 }
 
+impl std::fmt::Display for CardFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts : Vec<String> = self.types.iter().map(|t| format!("{:?}", t).to_lowercase()).collect();
+        parts.extend(self.excluded_types.iter().map(|t| format!("non{:?}", t).to_lowercase()));
+        if let Some(name) = &self.name {
+            parts.push(format!("name={}", name));
+        }
+        if let Some(tag) = &self.tag {
+            parts.push(format!("tag={}", tag));
+        }
+        if let Some(subtype) = &self.subtype {
+            parts.push(format!("subtype={}", subtype));
+        }
+        if let Some(min) = self.min_cmc {
+            parts.push(format!("cmc>={}", min));
+        }
+        if let Some(max) = self.max_cmc {
+            parts.push(format!("cmc<={}", max));
+        }
+        if parts.is_empty() {
+            return write!(f, "any");
+        }
+        write!(f, "{}", parts.join("/"))
+    }
+}
+
 impl std::fmt::Display for Effect {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Effect::ProduceMana(pool) => write!(f, "produce={}", pool),
             Effect::FetchLand { to_hand: hand, to_battlefield: bf } => write!(f, "fetch={}/{}", hand.len(), bf.len()),
             Effect::LandLimit(increase) => write!(f, "land-limit=+{}", increase),
-            Effect::Draw(ratios) => write!(f, "draw({:?})", ratios)
+            Effect::Draw(ratios) => write!(f, "draw({:?})", ratios),
+            Effect::Tutor { filter, destination, preferences: _ } => write!(f, "tutor({}->{:?})", filter, destination),
+            Effect::Scry(count) => write!(f, "scry({})", count),
+            Effect::Surveil(count) => write!(f, "surveil({})", count),
+            Effect::Dig { count, filter } => write!(f, "dig({}, {})", count, filter),
+            Effect::NoMaximumHandSize => write!(f, "no-max-hand-size"),
+            Effect::ReturnFromGraveyard { filter, destination, count } => write!(f, "return-from-graveyard({}x {}->{:?})", count, filter, destination),
+            Effect::PlayLandsFromGraveyard => write!(f, "play-lands-from-graveyard"),
+            Effect::CastFromGraveyard { exile: 0 } => write!(f, "cast-from-graveyard"),
+            Effect::CastFromGraveyard { exile } => write!(f, "cast-from-graveyard(exile={})", exile),
+            Effect::PlayLandsFromLibraryTop => write!(f, "play-lands-from-library-top"),
+            Effect::CostReduction { filter, amount, floor } => write!(f, "cost-reduction({}, {}, floor={})", filter, amount, floor),
+            Effect::CostIncrease { filter, count } => write!(f, "cost-increase({}, {})", filter, count),
+            Effect::Cascade => write!(f, "cascade"),
+            Effect::Discover(count) => write!(f, "discover({})", count),
+            Effect::ProduceManaPer { mana, amount } => write!(f, "produce={} per {}", mana, amount),
+            Effect::ManaMultiplier { filter, factor } => write!(f, "mana-multiplier({}, x{})", filter, factor),
+            Effect::UntapLands(count) => write!(f, "untap-lands({})", count),
+            Effect::CreateToken { token, count } => write!(f, "create-token({}x {})", count, token.name),
+            Effect::DrawPer(amount) => write!(f, "draw(per {})", amount),
+            Effect::AddCounters { counter, count, filter: Some(filter) } => write!(f, "add-counters({}x {} on {})", count, counter, filter),
+            Effect::AddCounters { counter, count, filter: None } => write!(f, "add-counters({}x {})", count, counter),
+            Effect::MoveCounters { counter, count, filter } => write!(f, "move-counters({}x {} on {})", count, counter, filter),
+            Effect::Proliferate => write!(f, "proliferate"),
+            Effect::PerOpponent { effect, chance } => write!(f, "per-opponent({}, {})", effect, chance)
+        }
+    }
+}
+
+impl std::fmt::Display for TableEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TableEvent::Draw => write!(f, "opponent-draws"),
+            TableEvent::SpellCast => write!(f, "opponent-spell-cast"),
+            TableEvent::SecondSpellCast => write!(f, "opponent-second-spell"),
+            TableEvent::CreatureDies => write!(f, "opponent-creature-dies")
+        }
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Phase::Untap => write!(f, "untap"),
+            Phase::Upkeep => write!(f, "upkeep"),
+            Phase::Draw => write!(f, "draw-step"),
+            Phase::FirstMain => write!(f, "main-1"),
+            Phase::Combat => write!(f, "combat"),
+            Phase::SecondMain => write!(f, "main-2"),
+            Phase::End => write!(f, "end-step"),
+            Phase::Cleanup => write!(f, "cleanup")
+        }
+    }
+}
+
+impl std::fmt::Display for CounterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CounterType::PlusOne => write!(f, "+1/+1"),
+            CounterType::MinusOne => write!(f, "-1/-1"),
+            CounterType::Loyalty => write!(f, "loyalty"),
+            CounterType::Charge => write!(f, "charge"),
+            CounterType::Oil => write!(f, "oil")
+        }
+    }
+}
+
+impl std::fmt::Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Amount::Count(filter) => write!(f, "count({})", filter),
+            Amount::Devotion(Some(color)) => write!(f, "devotion({:?})", color),
+            Amount::Devotion(None) => write!(f, "devotion"),
+            Amount::Counters(counter, filter) => write!(f, "counters({}, {})", counter, filter),
+            Amount::WithCounters(counter, filter) => write!(f, "with-counters({}, {})", counter, filter),
+            Amount::Expr(expr) => write!(f, "{}", expr)
         }
     }
 }
 
+impl Ability {
+    pub fn works_from(&self, zone: ZoneType) -> bool {
+        return self.zones.contains(zone);
+    }
+
+    // Converts mana in the pool rather than just adding to it, like signets,
+    // filter lands and 'Prismatic Lens', or turns life into mana, like
+    // 'Horizon Canopy'. These are used when paying, not up front.
+    pub fn is_mana_converter(&self) -> bool {
+        return self.trigger.is_activated()
+            && self.cost.is_tap()
+            && (self.cost.is_mana().is_some() || self.cost.is_life().is_some())
+            && self.effect.is_produce_mana();
+    }
+}
+
 impl std::fmt::Display for Ability {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Ability({} {} {})", self.effect, self.trigger, self.cost)
+        write!(f, "Ability({} {} {}", self.effect, self.trigger, self.cost)?;
+        if self.zones != ZoneType::Battlefield {
+            let zones : Vec<String> = self.zones.iter().map(|zone| zone.to_string()).collect();
+            write!(f, " from {}", zones.join("/"))?;
+        }
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -303,6 +757,73 @@ impl std::fmt::Display for AdditionalCost {
 
 impl CardData {
 
+    pub fn is_basic(&self) -> bool {
+        return self.supertypes.contains(Supertype::Basic);
+    }
+
+    pub fn has_subtype(&self, subtype : &str) -> bool {
+        return self.subtypes.iter().any(|s| s.eq_ignore_ascii_case(subtype));
+    }
+
+    // Matches type queries from fetch effects word by word, like "basic
+    // land", "forest" or "forest island" for a dual land with both basic land
+    // types. Alternatives are separated by '/', like "island/swamp".
+    pub fn matches_type_query(&self, query : &str) -> bool {
+        return query.to_lowercase().split('/').any(|alternative| {
+            alternative.split_whitespace().all(|word| match (parse_supertype(word), parse_type(word)) {
+                (Some(supertype), _) => self.supertypes.contains(supertype),
+                (None, Some(t)) => self.types.contains(t),
+                (None, None) => self.has_subtype(word)
+            })
+        });
+    }
+
+    // Tokens aren't in the card database, so they only have what the
+    // simulation cares about.
+    pub fn new_token(name : &str, type_string : &str) -> CardData {
+        let (supertypes, types, subtypes) = parse_type_line(type_string);
+        return CardData {
+            name: name.to_string(),
+            cmc: 0,
+            mana_cost: None,
+            type_string: type_string.to_string(),
+            supertypes,
+            types,
+            subtypes,
+            produced_mana: None,
+            enters_tapped: EntersTapped::Never,
+            haste: false,
+            loyalty: None,
+            abilities: None,
+            additional_cost: None,
+            tags: Vec::new()
+        };
+    }
+
+    // A card with just a name, a type line and a mana cost, like "{1}{G}" or
+    // "" for a land, the abilities are up to the test.
+    #[cfg(test)]
+    pub fn make_card_data(name : &str, type_string : &str, mana_cost : &str) -> CardData {
+        let mana_cost = ManaPool::new_from_string(mana_cost).ok();
+        let (supertypes, types, subtypes) = parse_type_line(type_string);
+        return CardData {
+            name: name.to_string(),
+            cmc: mana_cost.as_ref().map_or(0, |cost| cost.cmc()),
+            mana_cost,
+            type_string: type_string.to_string(),
+            supertypes,
+            types,
+            subtypes,
+            produced_mana: None,
+            enters_tapped: EntersTapped::Never,
+            haste: false,
+            loyalty: None,
+            abilities: None,
+            additional_cost: None,
+            tags: Vec::new()
+        };
+    }
+
     #[cfg(test)]
     pub fn make_sol_ring_data() -> CardData {
         return CardData {
@@ -311,15 +832,22 @@ impl CardData {
             mana_cost: Some(ManaPool::new_from_sequence(&vec![COLORLESS])),
             type_string: "Artifact".to_string(),
             types: enumflags2::make_bitflags!(Types::{Artifact}),
+            supertypes: BitFlags::empty(),
+            subtypes: Vec::new(),
             produced_mana: Some(COLORLESS),
-            enters_tapped: false,
+            enters_tapped: EntersTapped::Never,
+            haste: false,
+            loyalty: None,
             abilities: Some(vec![ Ability {
                 trigger: Trigger::Activated,
                 cost: Cost::Tap,
                 effect: Effect::ProduceMana(ManaPool::new_from_sequence(&vec![COLORLESS, COLORLESS])),
-                availability: 1.0
+                availability: 1.0,
+                zones: ZoneType::Battlefield.into(),
+                condition: None
             }]),
-            additional_cost: None
+            additional_cost: None,
+            tags: Vec::new()
         };
     }
 
@@ -331,15 +859,22 @@ impl CardData {
             mana_cost: Some(ManaPool::new_from_sequence(&vec![COLORLESS, COLORLESS, COLORLESS])),
             type_string: "Artifact".to_string(),
             types: enumflags2::make_bitflags!(Types::{Artifact}),
+            supertypes: BitFlags::empty(),
+            subtypes: Vec::new(),
             produced_mana: Some(ALL),
-            enters_tapped: false,
+            enters_tapped: EntersTapped::Never,
+            haste: false,
+            loyalty: None,
             abilities: Some(vec! [ Ability {
                 trigger: Trigger::Activated,
                 cost: Cost::Tap,
                 effect: Effect::ProduceMana(ManaPool::new_from_sequence(&vec![ALL])),
-                availability: 1.0
+                availability: 1.0,
+                zones: ZoneType::Battlefield.into(),
+                condition: None
             }]),
-            additional_cost: None
+            additional_cost: None,
+            tags: Vec::new()
         };
     }
 
@@ -349,17 +884,24 @@ impl CardData {
             name: "Plains".to_string(),
             cmc: 0,
             mana_cost: None,
-            type_string: "Basic Land".to_string(),
+            type_string: "Basic Land — Plains".to_string(),
             types: enumflags2::make_bitflags!(Types::{Land}),
+            supertypes: enumflags2::make_bitflags!(Supertype::{Basic}),
+            subtypes: vec!["plains".to_string()],
             produced_mana: Some(WHITE),
-            enters_tapped: false,
+            enters_tapped: EntersTapped::Never,
+            haste: false,
+            loyalty: None,
             abilities: Some(vec! [ Ability {
                 trigger: Trigger::Activated,
                 cost: Cost::Tap,
                 effect: Effect::ProduceMana(ManaPool::new_from_sequence(&vec![WHITE])),
-                availability: 1.0
+                availability: 1.0,
+                zones: ZoneType::Battlefield.into(),
+                condition: None
             }]),
-            additional_cost: None
+            additional_cost: None,
+            tags: Vec::new()
         };
     }
 
@@ -369,17 +911,24 @@ impl CardData {
             name: "Swamp".to_string(),
             cmc: 0,
             mana_cost: None,
-            type_string: "Basic Land".to_string(),
+            type_string: "Basic Land — Swamp".to_string(),
             types: enumflags2::make_bitflags!(Types::{Land}),
+            supertypes: enumflags2::make_bitflags!(Supertype::{Basic}),
+            subtypes: vec!["swamp".to_string()],
             produced_mana: Some(BLACK),
-            enters_tapped: false,
+            enters_tapped: EntersTapped::Never,
+            haste: false,
+            loyalty: None,
             abilities: Some(vec! [ Ability {
                 trigger: Trigger::Activated,
                 cost: Cost::Tap,
                 effect: Effect::ProduceMana(ManaPool::new_from_sequence(&vec![BLACK])),
-                availability: 1.0
+                availability: 1.0,
+                zones: ZoneType::Battlefield.into(),
+                condition: None
             }]),
-            additional_cost: None
+            additional_cost: None,
+            tags: Vec::new()
         };
     }
 
@@ -391,15 +940,22 @@ impl CardData {
             mana_cost: None,
             type_string: "Land".to_string(),
             types: enumflags2::make_bitflags!(Types::{Land}),
+            supertypes: BitFlags::empty(),
+            subtypes: Vec::new(),
             produced_mana: Some(ALL),
-            enters_tapped: false,
+            enters_tapped: EntersTapped::Never,
+            haste: false,
+            loyalty: None,
             abilities: Some(vec! [ Ability {
                 trigger: Trigger::Activated,
                 cost: Cost::Tap,
                 effect: Effect::ProduceMana(ManaPool::new_from_sequence(&vec![ALL])),
-                availability: 1.0
+                availability: 1.0,
+                zones: ZoneType::Battlefield.into(),
+                condition: None
             }]),
-            additional_cost: None
+            additional_cost: None,
+            tags: Vec::new()
         };
     }
 
@@ -411,15 +967,22 @@ impl CardData {
             mana_cost: None,
             type_string: "Land".to_string(),
             types: enumflags2::make_bitflags!(Types::{Land}),
+            supertypes: BitFlags::empty(),
+            subtypes: Vec::new(),
             produced_mana: Some(Mana::make_dual(Color::Black, Color::Green)),
-            enters_tapped: true,
+            enters_tapped: EntersTapped::Always,
+            haste: false,
+            loyalty: None,
             abilities: Some(vec![ Ability {
                 trigger: Trigger::Activated,
                 cost: Cost::Tap,
                 effect: Effect::ProduceMana(ManaPool::new_from_sequence(&vec![Mana::make_dual(Color::Black, Color::Green)])),
-                availability: 1.0
+                availability: 1.0,
+                zones: ZoneType::Battlefield.into(),
+                condition: None
             }]),
-            additional_cost: None
+            additional_cost: None,
+            tags: Vec::new()
         };
     }
 
@@ -431,10 +994,15 @@ impl CardData {
             mana_cost: Some(ManaPool::new_from_sequence(&vec![COLORLESS, COLORLESS, GREEN])),
             type_string: "Creature".to_string(),
             types: enumflags2::make_bitflags!(Types::{Creature}),
+            supertypes: BitFlags::empty(),
+            subtypes: Vec::new(),
             produced_mana: None,
-            enters_tapped: false,
+            enters_tapped: EntersTapped::Never,
+            haste: false,
+            loyalty: None,
             abilities: None,
-            additional_cost: None
+            additional_cost: None,
+            tags: Vec::new()
         };
     }
 }
@@ -451,6 +1019,8 @@ impl Cost {
             Cost::Tap => true,
             Cost::TapMana(_) => true,
             Cost::TapManaSacrifice(_) => true,
+            Cost::TapLife(_) => true,
+            Cost::TapSacrificeLife(_) => true,
             _ => false
         }
     }
@@ -459,6 +1029,7 @@ impl Cost {
             Cost::Mana(pool) => Some(pool),
             Cost::TapMana(pool) => Some(pool),
             Cost::TapManaSacrifice(pool) => Some(pool),
+            Cost::ManaLife(pool, _) => Some(pool),
             _ => None
         }
     }
@@ -467,9 +1038,25 @@ impl Cost {
             Cost::Sacrifice => true,
             Cost::TapSacrifice => true,
             Cost::TapManaSacrifice(_) => true,
+            Cost::TapSacrificeLife(_) => true,
             _ => false
         }
     }
+    pub fn is_loyalty(&self) -> Option<i32> {
+        match self {
+            Cost::Loyalty(loyalty) => Some(*loyalty),
+            _ => None
+        }
+    }
+    pub fn is_life(&self) -> Option<u32> {
+        match self {
+            Cost::Life(life) => Some(*life),
+            Cost::TapLife(life) => Some(*life),
+            Cost::ManaLife(_, life) => Some(*life),
+            Cost::TapSacrificeLife(life) => Some(*life),
+            _ => None
+        }
+    }
 }
 
 impl Trigger {
@@ -485,24 +1072,78 @@ impl Trigger {
             _ => false
         }
     }
-    pub fn is_upkeep(&self) -> bool {
+    pub fn is_static(&self) -> bool {
         match self {
-            Trigger::Upkeep => true,
+            Trigger::Static => true,
+            _ => false
+        }
+    }
+    pub fn is_beginning_of(&self, phase : Phase) -> bool {
+        match self {
+            Trigger::BeginningOf(p) => *p == phase,
+            Trigger::EachDrawStep => phase == Phase::Draw,
+            _ => false
+        }
+    }
+    pub fn is_opponent(&self, table_event : TableEvent) -> bool {
+        match self {
+            Trigger::Opponent(event) => *event == table_event,
+            _ => false
+        }
+    }
+    pub fn is_spell_cast(&self, spell : &CardData) -> bool {
+        match self {
+            Trigger::SpellCast(filter) => filter.matches(spell),
+            _ => false
+        }
+    }
+    pub fn is_zone_change(&self, from_zone : ZoneType, to_zone : ZoneType, card : &CardData) -> bool {
+        match self {
+            Trigger::ZoneChange { from, to, filter } =>
+                from.map_or(true, |zone| zone == from_zone)
+                && to.map_or(true, |zone| zone == to_zone)
+                && filter.matches(card),
             _ => false
         }
     }
 }
 
 impl Effect {
+    pub fn is_cost_modifier(&self) -> bool {
+        match self {
+            Effect::CostReduction { filter: _, amount: _, floor: _ } => true,
+            Effect::CostIncrease { filter: _, count: _ } => true,
+            _ => false
+        }
+    }
     pub fn is_produce_mana(&self) -> bool {
         match self {
             Effect::ProduceMana(_) => true,
             _ => false
         }
     }
+    pub fn is_produce_mana_per(&self) -> bool {
+        match self {
+            Effect::ProduceManaPer { mana: _, amount: _ } => true,
+            _ => false
+        }
+    }
     pub fn is_draw(&self) -> bool {
         match self {
             Effect::Draw(_) => true,
+            Effect::DrawPer(_) => true,
+            _ => false
+        }
+    }
+    pub fn is_dig(&self) -> bool {
+        match self {
+            Effect::Dig { count: _, filter: _ } => true,
+            _ => false
+        }
+    }
+    pub fn is_tutor(&self) -> bool {
+        match self {
+            Effect::Tutor { filter: _, destination: _, preferences: _ } => true,
             _ => false
         }
     }
@@ -512,6 +1153,26 @@ impl Effect {
             _ => false
         }
     }
+    pub fn is_add_counters(&self) -> bool {
+        match self {
+            Effect::AddCounters { counter: _, count: _, filter: _ } => true,
+            _ => false
+        }
+    }
+    pub fn is_cast_from_graveyard(&self) -> bool {
+        match self {
+            Effect::CastFromGraveyard { exile: _ } => true,
+            _ => false
+        }
+    }
+    pub fn is_counters(&self) -> bool {
+        match self {
+            Effect::AddCounters { counter: _, count: _, filter: _ } => true,
+            Effect::MoveCounters { counter: _, count: _, filter: _ } => true,
+            Effect::Proliferate => true,
+            _ => false
+        }
+    }
 }
 
 #[cfg(test)]
@@ -521,16 +1182,124 @@ mod tests {
 
     #[test]
     fn test_carddata_parse_types() {
-        assert_eq!(parse_types("something land something"), enumflags2::make_bitflags!(Types::{ Land }));
-        assert_eq!(parse_types("something creature something"), enumflags2::make_bitflags!(Types::{ Creature }));
-        assert_eq!(parse_types("something artifact something"), enumflags2::make_bitflags!(Types::{ Artifact }));
-        assert_eq!(parse_types("something planeswalker something"), enumflags2::make_bitflags!(Types::{ Planeswalker }));
-        assert_eq!(parse_types("something enchantment something"), enumflags2::make_bitflags!(Types::{ Enchantment }));
-        assert_eq!(parse_types("something sorcery something"), enumflags2::make_bitflags!(Types::{ Sorcery }));
-        assert_eq!(parse_types("something instant something"), enumflags2::make_bitflags!(Types::{ Instant }));
-        assert_eq!(parse_types("land creature - angel"), enumflags2::make_bitflags!(Types::{ Land | Creature }));
-        assert_eq!(parse_types("legendary artifact - equipment"), enumflags2::make_bitflags!(Types::{ Artifact }));
-        assert_eq!(parse_types("something land something"), enumflags2::make_bitflags!(Types::{ Land }));
-        assert_eq!(parse_types("legendary enchantment creature"), enumflags2::make_bitflags!(Types::{ Creature | Enchantment }));
+        assert_eq!(parse_type_line("something land something").1, enumflags2::make_bitflags!(Types::{ Land }));
+        assert_eq!(parse_type_line("something creature something").1, enumflags2::make_bitflags!(Types::{ Creature }));
+        assert_eq!(parse_type_line("something artifact something").1, enumflags2::make_bitflags!(Types::{ Artifact }));
+        assert_eq!(parse_type_line("something planeswalker something").1, enumflags2::make_bitflags!(Types::{ Planeswalker }));
+        assert_eq!(parse_type_line("something enchantment something").1, enumflags2::make_bitflags!(Types::{ Enchantment }));
+        assert_eq!(parse_type_line("something sorcery something").1, enumflags2::make_bitflags!(Types::{ Sorcery }));
+        assert_eq!(parse_type_line("something instant something").1, enumflags2::make_bitflags!(Types::{ Instant }));
+        assert_eq!(parse_type_line("land creature - angel").1, enumflags2::make_bitflags!(Types::{ Land | Creature }));
+        assert_eq!(parse_type_line("legendary artifact - equipment").1, enumflags2::make_bitflags!(Types::{ Artifact }));
+        assert_eq!(parse_type_line("something land something").1, enumflags2::make_bitflags!(Types::{ Land }));
+        assert_eq!(parse_type_line("legendary enchantment creature").1, enumflags2::make_bitflags!(Types::{ Creature | Enchantment }));
+    }
+
+    #[test]
+    fn test_carddata_parse_type_line() {
+        let (supertypes, types, subtypes) = parse_type_line("Legendary Snow Creature — Elf Druid");
+        assert_eq!(supertypes, enumflags2::make_bitflags!(Supertype::{ Legendary | Snow }));
+        assert_eq!(types, enumflags2::make_bitflags!(Types::{ Creature }));
+        assert_eq!(subtypes, vec!["elf", "druid"]);
+
+        let (supertypes, types, subtypes) = parse_type_line("Kindred Artifact — Dragon // Land — Forest");
+        assert_eq!(supertypes, BitFlags::empty());
+        assert_eq!(types, enumflags2::make_bitflags!(Types::{ Artifact | Land }));
+        assert_eq!(subtypes, vec!["dragon", "forest"]);
+
+        let dual = CardData::make_card_data("Breeding Pool", "Land — Forest Island", "");
+        assert!(dual.matches_type_query("forest island"));
+        assert!(dual.matches_type_query("island"));
+        assert!(dual.matches_type_query("mountain/forest"));
+        assert!(!dual.matches_type_query("basic land"));
+        assert!(!dual.matches_type_query("forest swamp"));
+        assert!(CardData::make_card_data("Snow-Covered Forest", "Basic Snow Land — Forest", "").matches_type_query("basic land"));
+        assert!(!CardData::make_card_data("Forest Dryad", "Creature — Dryad", "{2}{G}").matches_type_query("forest"));
+    }
+
+    #[test]
+    fn test_card_filter() {
+        let elk = CardData::make_elk_data();
+        let sol_ring = CardData::make_sol_ring_data();
+
+        let creature = parse_card_filter("creature").unwrap();
+        assert!(creature.matches(&elk));
+        assert!(!creature.matches(&sol_ring));
+
+        let noncreature = parse_card_filter("noncreature").unwrap();
+        assert!(!noncreature.matches(&elk));
+        assert!(noncreature.matches(&sol_ring));
+
+        let artifact_or_creature = parse_card_filter("artifact/creature").unwrap();
+        assert!(artifact_or_creature.matches(&elk));
+        assert!(artifact_or_creature.matches(&sol_ring));
+
+        assert!(parse_card_filter("any").unwrap().matches(&elk));
+        assert!(parse_card_filter("dragon").is_err());
+
+        let mut cheap_artifact = parse_card_filter("artifact").unwrap();
+        cheap_artifact.max_cmc = Some(1);
+        assert!(cheap_artifact.matches(&sol_ring));
+        cheap_artifact.max_cmc = Some(0);
+        assert!(!cheap_artifact.matches(&sol_ring));
+
+        let mut named = CardFilter::any();
+        named.name = Some("sol ring".to_string());
+        assert!(named.matches(&sol_ring));
+        assert!(!named.matches(&elk));
+
+        let mut tagged = CardFilter::any();
+        tagged.tag = Some("combo".to_string());
+        assert!(!tagged.matches(&elk));
+        let mut combo_elk = CardData::make_elk_data();
+        combo_elk.tags.push("Combo".to_string());
+        assert!(tagged.matches(&combo_elk));
+    }
+
+    #[test]
+    fn test_card_summoning_sickness() {
+        let elk_data = CardData::make_elk_data();
+        let mut elk = Card::new_with_id(1, &elk_data);
+        elk.entered_battlefield = 3;
+        assert!(elk.is_summoning_sick(3));
+        assert!(!elk.is_summoning_sick(4));
+
+        let mut hasty_elk_data = CardData::make_elk_data();
+        hasty_elk_data.haste = true;
+        let mut hasty_elk = Card::new_with_id(2, &hasty_elk_data);
+        hasty_elk.entered_battlefield = 3;
+        assert!(!hasty_elk.is_summoning_sick(3));
+
+        let sol_ring_data = CardData::make_sol_ring_data();
+        let mut sol_ring = Card::new_with_id(3, &sol_ring_data);
+        sol_ring.entered_battlefield = 3;
+        assert!(!sol_ring.is_summoning_sick(3));
+    }
+
+    #[test]
+    fn test_card_counters() {
+        let elk_data = CardData::make_elk_data();
+        let mut elk = Card::new_with_id(1, &elk_data);
+        elk.add_counters(CounterType::PlusOne, 2);
+        elk.add_counters(CounterType::Oil, 1);
+        assert_eq!(elk.counters(CounterType::PlusOne), 2);
+        elk.add_counters(CounterType::MinusOne, 3);
+        assert_eq!(elk.counters(CounterType::PlusOne), 0);
+        assert_eq!(elk.counters(CounterType::MinusOne), 1);
+        assert_eq!(elk.remove_counters(CounterType::Oil, 5), 1);
+        assert_eq!(elk.counters.len(), 1);
+        for counter in [CounterType::PlusOne, CounterType::MinusOne, CounterType::Loyalty, CounterType::Charge, CounterType::Oil] {
+            assert_eq!(parse_counter_type(&counter.to_string()), Some(counter));
+        }
+    }
+
+    #[test]
+    fn test_card_parse_phase() {
+        for phase in PHASES {
+            assert_eq!(parse_phase(&phase.to_string()), Some(phase));
+        }
+        assert_eq!(parse_phase("upkeep"), Some(Phase::Upkeep));
+        assert_eq!(parse_phase("end-step"), Some(Phase::End));
+        assert_eq!(parse_phase("second breakfast"), None);
     }
 }
@@ -1,6 +1,9 @@
 use crate::mana;
 use crate::card;
 use crate::oracle;
+use crate::zone;
+use crate::expr;
+use enumflags2::BitFlags;
 
 pub struct DB {
     pub verbose : bool,
@@ -24,12 +27,8 @@ fn parse_produced_mana(value : &json::JsonValue) -> Option<mana::Mana> {
     return None;
 }
 
-fn parse_enters_tapped(name : &str, oracle_text : &str) -> bool {
-    let pattern = format!("{} enters the battlefield tapped.", name);
-    match oracle_text.to_lowercase().find(pattern.as_str()) {
-        Some(_) => return true,
-        None => return false
-    }
+fn parse_keyword(keywords : &json::JsonValue, keyword : &str) -> bool {
+    return keywords.members().any(|k| k.as_str() == Some(keyword));
 }
 
 fn parse_mana_pool(object: &json::object::Object, property: &str) -> Result<mana::ManaPool, String> {
@@ -39,6 +38,13 @@ fn parse_mana_pool(object: &json::object::Object, property: &str) -> Result<mana
     }
 }
 
+fn parse_life(object : &json::object::Object) -> Result<u32, String> {
+    match object["life"].as_u32() {
+        Some(life) => Ok(life),
+        None => Err("invalid 'cost::life' value".to_string())
+    }
+}
+
 fn parse_cost(object : &json::JsonValue) -> Result<card::Cost, String> {
     fn parse_cost_string(string : &str) -> Result<card::Cost, String> {
         match string {
@@ -61,6 +67,14 @@ fn parse_cost(object : &json::JsonValue) -> Result<card::Cost, String> {
                 Some("mana") => Ok(card::Cost::Mana(parse_mana_pool(cost_object, "mana")?)),
                 Some("tap-mana") => Ok(card::Cost::TapMana(parse_mana_pool(cost_object, "mana")?)),
                 Some("tap-mana-sacrifice") => Ok(card::Cost::TapManaSacrifice(parse_mana_pool(cost_object, "mana")?)),
+                Some("life") => Ok(card::Cost::Life(parse_life(cost_object)?)),
+                Some("tap-life") => Ok(card::Cost::TapLife(parse_life(cost_object)?)),
+                Some("mana-life") => Ok(card::Cost::ManaLife(parse_mana_pool(cost_object, "mana")?, parse_life(cost_object)?)),
+                Some("tap-sacrifice-life") => Ok(card::Cost::TapSacrificeLife(parse_life(cost_object)?)),
+                Some("loyalty") => match cost_object["loyalty"].as_i32() {
+                    Some(loyalty) => Ok(card::Cost::Loyalty(loyalty)),
+                    None => Err("invalid 'cost::loyalty' value".to_string())
+                },
                 _ => Err("invalid 'cost::type' value...".to_string()),
             }
         },
@@ -69,18 +83,56 @@ fn parse_cost(object : &json::JsonValue) -> Result<card::Cost, String> {
     }
 }
 
+fn parse_trigger_zone(value : &json::JsonValue) -> Result<Option<zone::ZoneType>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    return match value.as_str().and_then(zone::parse_zone_type) {
+        Some(zone_type) => Ok(Some(zone_type)),
+        None => Err("invalid 'trigger' zone".to_string())
+    };
+}
+
 fn parse_trigger(object : &json::JsonValue) -> Result<card::Trigger, String> {
     fn parse_trigger_string(string : &str) -> Result<card::Trigger, String> {
         match string {
             "activated" => Ok(card::Trigger::Activated),
-            "upkeep" => Ok(card::Trigger::Upkeep),
+            "static" => Ok(card::Trigger::Static),
             "cast" => Ok(card::Trigger::Cast),
-            _ => Err("invalid 'trigger' string".to_string())
+            "spell-cast" => Ok(card::Trigger::SpellCast(card::CardFilter::any())),
+            "each-draw-step" => Ok(card::Trigger::EachDrawStep),
+            "landfall" => Ok(card::Trigger::ZoneChange {
+                from: None,
+                to: Some(zone::ZoneType::Battlefield),
+                filter: card::parse_card_filter("land")?
+            }),
+            _ => match (card::parse_phase(string), card::parse_table_event(string)) {
+                (Some(phase), _) => Ok(card::Trigger::BeginningOf(phase)),
+                (None, Some(event)) => Ok(card::Trigger::Opponent(event)),
+                (None, None) => Err("invalid 'trigger' string".to_string())
+            }
         }
     }
     match &object["trigger"] {
         json::JsonValue::Short(txt) => parse_trigger_string(txt),
         json::JsonValue::String(txt) => parse_trigger_string(txt),
+        json::JsonValue::Object(trigger_object) => {
+            match trigger_object["type"].as_str() {
+                Some("spell-cast") => Ok(card::Trigger::SpellCast(parse_card_filter(&trigger_object["filter"])?)),
+                Some("zone-change") => Ok(card::Trigger::ZoneChange {
+                    from: parse_trigger_zone(&trigger_object["from"])?,
+                    to: parse_trigger_zone(&trigger_object["to"])?,
+                    filter: parse_card_filter(&trigger_object["filter"])?
+                }),
+                Some("dies") => Ok(card::Trigger::ZoneChange {
+                    from: Some(zone::ZoneType::Battlefield),
+                    to: Some(zone::ZoneType::Graveyard),
+                    filter: card::parse_card_filter(trigger_object["filter"].as_str().unwrap_or("creature"))?
+                }),
+                Some(txt) => parse_trigger_string(txt),
+                None => Err("invalid 'trigger::type' value...".to_string())
+            }
+        },
         _ => Err("invalid 'trigger' value!".to_string())
     }
 }
@@ -106,7 +158,13 @@ fn parse_effect_land_fetch(object : &json::object::Object) -> Result<card::Effec
 }
 
 fn parse_effect_draw(object: &json::object::Object) -> Result<card::Effect, String> {
+    if !object["amount"].is_null() {
+        return Ok(card::Effect::DrawPer(parse_amount(&object["amount"])?));
+    }
     let json_count = &object["count"];
+    if json_count.is_string() {
+        return Ok(card::Effect::DrawPer(parse_amount(json_count)?));
+    }
     if json_count.is_array() {
         let mut draw_ratios: Vec<u32> = Vec::new();
         for value in json_count.members() {
@@ -126,14 +184,185 @@ fn parse_effect_land_limit(object: &json::object::Object) -> Result<card::Effect
     return Err("invalid 'increase' in land-limit".to_string())
 }
 
+fn parse_card_filter(value : &json::JsonValue) -> Result<card::CardFilter, String> {
+    match value {
+        json::JsonValue::Null => Ok(card::CardFilter::any()),
+        json::JsonValue::Short(txt) => card::parse_card_filter(txt),
+        json::JsonValue::String(txt) => card::parse_card_filter(txt),
+        json::JsonValue::Object(object) => {
+            let mut filter = match object["types"].as_str() {
+                Some(types) => card::parse_card_filter(types)?,
+                None => card::CardFilter::any()
+            };
+            filter.name = object["name"].as_str().map(|n| n.to_string());
+            filter.tag = object["tag"].as_str().map(|t| t.to_string());
+            filter.subtype = object["subtype"].as_str().map(|t| t.to_string());
+            filter.min_cmc = object["min-cmc"].as_u32();
+            filter.max_cmc = object["max-cmc"].as_u32();
+            Ok(filter)
+        },
+        _ => Err("invalid 'filter' value".to_string())
+    }
+}
+
+fn parse_counter_type(value : &json::JsonValue) -> Result<card::CounterType, String> {
+    return value.as_str()
+        .and_then(card::parse_counter_type)
+        .ok_or("invalid 'counter' type".to_string());
+}
+
+fn parse_amount(value : &json::JsonValue) -> Result<card::Amount, String> {
+    if let Some(text) = value.as_str() {
+        return Ok(card::Amount::Expr(expr::parse(text)?));
+    }
+    if let Some(number) = value.as_u32() {
+        return Ok(card::Amount::Expr(expr::Expr::Number(number as i64)));
+    }
+    if !value["count"].is_null() {
+        return Ok(card::Amount::Count(parse_card_filter(&value["count"])?));
+    }
+    if !value["counters"].is_null() {
+        return Ok(card::Amount::Counters(parse_counter_type(&value["counters"])?, parse_card_filter(&value["filter"])?));
+    }
+    if !value["with-counters"].is_null() {
+        return Ok(card::Amount::WithCounters(parse_counter_type(&value["with-counters"])?, parse_card_filter(&value["filter"])?));
+    }
+    match value["devotion"].as_str() {
+        Some("any") => Ok(card::Amount::Devotion(None)),
+        Some(color) => match mana::parse_color(color) {
+            Some(color) => Ok(card::Amount::Devotion(Some(color))),
+            None => Err("invalid 'devotion' color".to_string())
+        },
+        None => Err("invalid 'amount' value".to_string())
+    }
+}
+
+// Fixed amounts of mana, or an amount depending on the board when there is
+// an 'amount', like { "count": "creature" }.
+fn parse_effect_mana(object : &json::object::Object) -> Result<card::Effect, String> {
+    let mana = parse_mana_pool(object, "produce")?;
+    if object["amount"].is_null() {
+        return Ok(card::Effect::ProduceMana(mana));
+    }
+    return Ok(card::Effect::ProduceManaPer { mana, amount: parse_amount(&object["amount"])? });
+}
+
+fn parse_destination(value : &json::JsonValue) -> Result<card::Destination, String> {
+    match value.as_str() {
+        Some("hand") | None => Ok(card::Destination::Hand),
+        Some("library-top") => Ok(card::Destination::LibraryTop),
+        Some("battlefield") => Ok(card::Destination::Battlefield),
+        _ => Err("invalid 'to' value".to_string())
+    }
+}
+
+fn parse_effect_tutor(object: &json::object::Object) -> Result<card::Effect, String> {
+    let mut preferences : Vec<card::TutorPreference> = Vec::new();
+    for value in object["priority"].members() {
+        match value.as_str() {
+            Some("missing-color-source") => preferences.push(card::TutorPreference::MissingColorSource),
+            Some(name) => {
+                let mut filter = card::CardFilter::any();
+                filter.name = Some(name.to_string());
+                preferences.push(card::TutorPreference::Matching(filter));
+            },
+            None => preferences.push(card::TutorPreference::Matching(parse_card_filter(value)?))
+        }
+    }
+    return Ok(card::Effect::Tutor {
+        filter: parse_card_filter(&object["filter"])?,
+        destination: parse_destination(&object["to"])?,
+        preferences
+    });
+}
+
+fn parse_effect_count(object: &json::object::Object) -> Result<u32, String> {
+    return object["count"].as_u32().ok_or("invalid 'count' value".to_string());
+}
+
+fn parse_effect_dig(object: &json::object::Object) -> Result<card::Effect, String> {
+    return Ok(card::Effect::Dig {
+        count: parse_effect_count(object)?,
+        filter: parse_card_filter(&object["filter"])?
+    });
+}
+
+fn parse_effect_return_from_graveyard(object: &json::object::Object) -> Result<card::Effect, String> {
+    return Ok(card::Effect::ReturnFromGraveyard {
+        filter: parse_card_filter(&object["filter"])?,
+        destination: parse_destination(&object["to"])?,
+        count: object["count"].as_u32().unwrap_or(1)
+    });
+}
+
+// "token" is the type line without the 'Token' prefix, like "Creature — Beast".
+fn parse_effect_create_token(object : &json::object::Object) -> Result<card::Effect, String> {
+    let type_string = match object["token"].as_str() {
+        Some(token) => format!("Token {}", token),
+        None => return Err("invalid 'effect::token' string".to_string())
+    };
+    let name = match object["name"].as_str() {
+        Some(name) => name.to_string(),
+        None => format!("{} Token", type_string.rsplit(' ').next().unwrap())
+    };
+    return Ok(card::Effect::CreateToken {
+        token: Box::new(card::CardData::new_token(&name, &type_string)),
+        count: object["count"].as_u32().unwrap_or(1)
+    });
+}
+
 fn parse_effect(object : &json::JsonValue) -> Result<card::Effect, String> {
     match &object["effect"] {
         json::JsonValue::Object(effect_object) => {
             match effect_object["type"].as_str() {
-                Some("mana") => Ok(card::Effect::ProduceMana(parse_mana_pool(effect_object, "produce")?)),
+                Some("mana") => parse_effect_mana(effect_object),
                 Some("land-fetch") => parse_effect_land_fetch(effect_object),
                 Some("draw") => parse_effect_draw(effect_object),
                 Some("land-limit") => parse_effect_land_limit(effect_object),
+                Some("tutor") => parse_effect_tutor(effect_object),
+                Some("scry") => Ok(card::Effect::Scry(parse_effect_count(effect_object)?)),
+                Some("surveil") => Ok(card::Effect::Surveil(parse_effect_count(effect_object)?)),
+                Some("dig") => parse_effect_dig(effect_object),
+                Some("no-max-hand-size") => Ok(card::Effect::NoMaximumHandSize),
+                Some("return-from-graveyard") => parse_effect_return_from_graveyard(effect_object),
+                Some("play-lands-from-graveyard") => Ok(card::Effect::PlayLandsFromGraveyard),
+                Some("cast-from-graveyard") => Ok(card::Effect::CastFromGraveyard { exile: effect_object["exile"].as_u32().unwrap_or(0) }),
+                Some("play-lands-from-library-top") => Ok(card::Effect::PlayLandsFromLibraryTop),
+                Some("cost-reduction") => Ok(card::Effect::CostReduction {
+                    filter: parse_card_filter(&effect_object["filter"])?,
+                    amount: parse_amount(&effect_object["amount"])?,
+                    floor: effect_object["floor"].as_u32().unwrap_or(0)
+                }),
+                Some("cost-increase") => Ok(card::Effect::CostIncrease {
+                    filter: parse_card_filter(&effect_object["filter"])?,
+                    count: parse_effect_count(effect_object)?
+                }),
+                Some("mana-multiplier") => Ok(card::Effect::ManaMultiplier {
+                    filter: parse_card_filter(&effect_object["filter"])?,
+                    factor: effect_object["factor"].as_u32().unwrap_or(2)
+                }),
+                Some("cascade") => Ok(card::Effect::Cascade),
+                Some("discover") => Ok(card::Effect::Discover(parse_effect_count(effect_object)?)),
+                Some("untap-lands") => Ok(card::Effect::UntapLands(parse_effect_count(effect_object)?)),
+                Some("create-token") => parse_effect_create_token(effect_object),
+                Some("add-counters") => Ok(card::Effect::AddCounters {
+                    counter: parse_counter_type(&effect_object["counter"])?,
+                    count: effect_object["count"].as_u32().unwrap_or(1),
+                    filter: match effect_object["filter"].is_null() {
+                        true => None,
+                        false => Some(parse_card_filter(&effect_object["filter"])?)
+                    }
+                }),
+                Some("move-counters") => Ok(card::Effect::MoveCounters {
+                    counter: parse_counter_type(&effect_object["counter"])?,
+                    count: effect_object["count"].as_u32().unwrap_or(1),
+                    filter: parse_card_filter(&effect_object["filter"])?
+                }),
+                Some("proliferate") => Ok(card::Effect::Proliferate),
+                Some("per-opponent") => Ok(card::Effect::PerOpponent {
+                    effect: Box::new(parse_effect(&object["effect"])?),
+                    chance: effect_object["chance"].as_f32().unwrap_or(1.0)
+                }),
                 _ => Err("invalid 'effect::type' string".to_string())
             }
         },
@@ -148,12 +377,35 @@ fn parse_availability(object : &json::JsonValue) -> f32 {
     return 1.0;
 }
 
+// The zones an ability works from, either a single zone like "hand" or a
+// list of zones like [ "battlefield", "command" ].
+fn parse_zones(object : &json::JsonValue) -> Result<BitFlags<zone::ZoneType>, String> {
+    let value = &object["zone"];
+    if value.is_null() {
+        return Ok(zone::ZoneType::Battlefield.into());
+    }
+    let names : Vec<&json::JsonValue> = if value.is_array() { value.members().collect() } else { vec![value] };
+    let mut zones = BitFlags::empty();
+    for name in names {
+        match name.as_str().and_then(zone::parse_zone_type) {
+            Some(zone_type) => zones |= zone_type,
+            None => return Err("invalid 'zone' string".to_string())
+        }
+    }
+    return Ok(zones);
+}
+
 fn parse_ability(object : &json::JsonValue) -> Result<card::Ability, String> {
     return Ok(card::Ability {
         trigger: parse_trigger(object)?,
         cost : parse_cost(object)?,
         effect : parse_effect(object)?,
-        availability : parse_availability(object)
+        availability : parse_availability(object),
+        zones : parse_zones(object)?,
+        condition : match object["condition"].as_str() {
+            Some(text) => Some(expr::parse(text)?),
+            None => None
+        }
     });
 }
 
@@ -169,10 +421,14 @@ fn parse_card_metadata(card : &mut card::CardData, object : &json::JsonValue) ->
             abilities.push(parse_ability(ability)?);
         }
         card.abilities = Some(abilities);
-    } else {
+    } else if object.has_key("trigger") {
         card.abilities = Some(vec![parse_ability(object)?]);
     }
 
+    for tag in object["tags"].members() {
+        card.tags.push(tag.to_string());
+    }
+
     return Ok(());
 }
 
@@ -212,6 +468,7 @@ impl DB {
         let type_line = json_object["type_line"].to_string();
         let card_name = json_object["name"].to_string();
 
+        let (supertypes, types, subtypes) = card::parse_type_line(&type_line);
         let mut entry = card::CardData {
             name: card_name.clone(),
             cmc: json_object["cmc"].as_f32().expect("cmc is not a number!") as u32,
@@ -220,35 +477,44 @@ impl DB {
                 Err(_) => None
             },
             type_string: type_line.clone(),
-            types: card::parse_types(&type_line),
+            supertypes,
+            types,
+            subtypes,
             produced_mana: parse_produced_mana(&json_object["produced_mana"]),
-            enters_tapped: parse_enters_tapped(&name, &json_object["oracle_text"].to_string()),
+            enters_tapped: oracle::parse_enters_tapped(&oracle::Context {
+                text: &json_object["oracle_text"].to_string(),
+                card_name: &card_name
+            }),
+            haste: parse_keyword(&json_object["keywords"], "Haste"),
+            loyalty: json_object["loyalty"].as_str().and_then(|l| l.parse::<u32>().ok()),
             abilities: None,
-            additional_cost: None
+            additional_cost: None,
+            tags: Vec::new()
         };
 
-        match self.metadata.get(name) {
-            Some(metadata) => {
-                match parse_card_metadata(&mut entry, metadata) {
-                    Err(failure) => {
-                        panic!("failed to parse metadata for {:?}, error: {:?}, json: {:?}",
-                               name,
-                               failure,
-                               metadata);
-                    },
-                    Ok(_) => { }
-                }
-            },
-            None => {
-                let ctx = oracle::Context {
-                    text: &json_object["oracle_text"].to_string(),
-                    card_name: &card_name
-                };
-                entry.abilities = oracle::parse(&ctx);
-                entry.additional_cost = oracle::parse_additional_cost(&ctx);
+        if let Some(metadata) = self.metadata.get(name) {
+            match parse_card_metadata(&mut entry, metadata) {
+                Err(failure) => {
+                    panic!("failed to parse metadata for {:?}, error: {:?}, json: {:?}",
+                           name,
+                           failure,
+                           metadata);
+                },
+                Ok(_) => { }
             }
         }
 
+        // Metadata may only carry tags, fall back to the oracle text for
+        // the abilities in that case.
+        if entry.abilities.is_none() {
+            let ctx = oracle::Context {
+                text: &json_object["oracle_text"].to_string(),
+                card_name: &card_name
+            };
+            entry.abilities = oracle::parse(&ctx);
+            entry.additional_cost = oracle::parse_additional_cost(&ctx);
+        }
+
         if self.verbose {
             println!(" -> {}", entry);
         }
@@ -273,6 +539,16 @@ impl DB {
         }
     }
 
+    pub fn add_tags(&mut self, name : &str, tags : &Vec<String>) {
+        if let Some(entry) = self.entries.get_mut(name) {
+            for tag in tags {
+                if !entry.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    entry.tags.push(tag.clone());
+                }
+            }
+        }
+    }
+
     pub fn alias(&self, name : &str) -> Option<String> {
         let json = self.metadata.get(name)?;
         return Some(json["alias"].as_str()?.to_string().to_lowercase());
@@ -286,7 +562,8 @@ impl DB {
         -> Card name, exact match
 
     * "enters_tapped" : bool
-        -> self explanatory.
+        -> self explanatory, parsed from the oracle text including
+           conditions like "unless you control a Forest or an Island".
 
     * "ramp" : String
         -> used ot indicate ramp spells, will be one of the following:
@@ -325,3 +602,24 @@ impl DB {
 
 */
 
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_carddb_parse_spell_cast_filter() {
+        let edgar = json::parse(r#"{ "trigger": { "type": "spell-cast", "filter": { "subtype": "vampire" } },
+            "zone": [ "battlefield", "command" ], "effect": { "type": "draw", "count": 1 } }"#).unwrap();
+        let ability = parse_ability(&edgar).unwrap();
+        assert_eq!(ability.zones, zone::ZoneType::Battlefield | zone::ZoneType::Command);
+        assert!(ability.trigger.is_spell_cast(&card::CardData::make_card_data("Vampire", "Creature — Vampire", "{1}{B}")));
+        assert!(!ability.trigger.is_spell_cast(&card::CardData::make_card_data("Elk", "Creature — Elk", "{2}{G}")));
+
+        let beast_whisperer = json::parse(r#"{ "trigger": { "type": "spell-cast", "filter": "creature" },
+            "effect": { "type": "draw", "count": 1 } }"#).unwrap();
+        let ability = parse_ability(&beast_whisperer).unwrap();
+        assert!(ability.trigger.is_spell_cast(&card::CardData::make_card_data("Elk", "Creature — Elk", "{2}{G}")));
+        assert!(!ability.trigger.is_spell_cast(&card::CardData::new_token("Treasure", "Artifact — Treasure")));
+    }
+}
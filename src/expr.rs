@@ -0,0 +1,437 @@
+use crate::card::{Amount, CardFilter, parse_card_filter, parse_counter_type};
+use crate::mana::parse_color;
+use crate::zone::{ZoneType, parse_zone_type};
+
+// Small expressions in the metadata, evaluated against the game state, like
+// "count(battlefield, creature) >= 3" or "hand_size - 2". Everything is an
+// integer, comparisons and logic operators give 1 or 0.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variable {
+    HandSize,
+    LibrarySize,
+    GraveyardSize,
+    LandsOnBattlefield,
+    LandsPlayed,
+    ManaAvailable,
+    Life,
+    Turn,
+    Opponents,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+    Min,
+    Max,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Variable(Variable),
+    Count(ZoneType, CardFilter),    // count(zone, filter), the zone defaults to the battlefield
+    Amount(Box<Amount>),            // devotion(color), counters(type, filter), with_counters(type, filter)
+    Random(Box<Expr>),              // random(n) is 0 up to n - 1
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+}
+
+// What expressions can ask the game about.
+pub trait Environment {
+    fn variable(&self, variable: Variable) -> i64;
+    fn count_in(&self, zone: ZoneType, filter: &CardFilter) -> i64;
+    fn amount(&self, amount: &Amount) -> i64;
+}
+
+pub fn parse_variable(name : &str) -> Option<Variable> {
+    match name {
+        "hand_size" => Some(Variable::HandSize),
+        "library_size" => Some(Variable::LibrarySize),
+        "graveyard_size" => Some(Variable::GraveyardSize),
+        "lands_on_battlefield" => Some(Variable::LandsOnBattlefield),
+        "lands_played" => Some(Variable::LandsPlayed),
+        "mana_available" => Some(Variable::ManaAvailable),
+        "life" => Some(Variable::Life),
+        "turn" => Some(Variable::Turn),
+        "opponents" => Some(Variable::Opponents),
+        _ => None
+    }
+}
+
+pub fn parse(text : &str) -> Result<Expr, String> {
+    let mut parser = Parser { text, pos: 0 };
+    let expr = parser.parse_or()?;
+    parser.skip_whitespace();
+    if parser.pos != text.len() {
+        return Err(format!("unexpected '{}' in expression '{}'", parser.rest(), text));
+    }
+    return Ok(expr);
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+
+    fn rest(&self) -> &'a str {
+        return &self.text[self.pos..];
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.text.len() - trimmed.len();
+    }
+
+    fn eat(&mut self, token : &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            return true;
+        }
+        return false;
+    }
+
+    fn expect(&mut self, token : &str) -> Result<(), String> {
+        if !self.eat(token) {
+            return Err(format!("expected '{}' in expression '{}'", token, self.text));
+        }
+        return Ok(());
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.eat("||") {
+            expr = Expr::Binary(Operator::Or, Box::new(expr), Box::new(self.parse_and()?));
+        }
+        return Ok(expr);
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_comparison()?;
+        while self.eat("&&") {
+            expr = Expr::Binary(Operator::And, Box::new(expr), Box::new(self.parse_comparison()?));
+        }
+        return Ok(expr);
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let expr = self.parse_sum()?;
+        // The two character operators go first, so "<=" isn't taken for "<".
+        for (token, operator) in [
+            ("<=", Operator::LessOrEqual),
+            (">=", Operator::GreaterOrEqual),
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater)] {
+            if self.eat(token) {
+                return Ok(Expr::Binary(operator, Box::new(expr), Box::new(self.parse_sum()?)));
+            }
+        }
+        return Ok(expr);
+    }
+
+    fn parse_sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_product()?;
+        loop {
+            if self.eat("+") {
+                expr = Expr::Binary(Operator::Add, Box::new(expr), Box::new(self.parse_product()?));
+            } else if self.eat("-") {
+                expr = Expr::Binary(Operator::Subtract, Box::new(expr), Box::new(self.parse_product()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            if self.eat("*") {
+                expr = Expr::Binary(Operator::Multiply, Box::new(expr), Box::new(self.parse_unary()?));
+            } else if self.eat("/") {
+                expr = Expr::Binary(Operator::Divide, Box::new(expr), Box::new(self.parse_unary()?));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.parse_unary()?)));
+        } else if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        return self.parse_primary();
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        if self.eat("(") {
+            let expr = self.parse_or()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+
+        let rest = self.rest();
+        let length = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        if length == 0 {
+            return Err(format!("unexpected '{}' in expression '{}'", rest, self.text));
+        }
+        let word = &rest[..length];
+        self.pos += length;
+
+        if let Ok(number) = word.parse::<i64>() {
+            return Ok(Expr::Number(number));
+        }
+        if self.eat("(") {
+            let arguments = self.parse_arguments()?;
+            return self.make_call(word, &arguments);
+        }
+        return match parse_variable(word) {
+            Some(variable) => Ok(Expr::Variable(variable)),
+            None => Err(format!("unknown variable '{}' in expression '{}'", word, self.text))
+        };
+    }
+
+    // The arguments are kept as text, as card filters like "instant/sorcery"
+    // and counters like "+1/+1" aren't expressions.
+    fn parse_arguments(&mut self) -> Result<Vec<&'a str>, String> {
+        let rest = self.rest();
+        let mut arguments : Vec<&'a str> = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (index, c) in rest.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                ',' if depth == 0 => {
+                    arguments.push(rest[start..index].trim());
+                    start = index + 1;
+                },
+                ')' => {
+                    let last = rest[start..index].trim();
+                    if !last.is_empty() || !arguments.is_empty() {
+                        arguments.push(last);
+                    }
+                    self.pos += index + 1;
+                    return Ok(arguments);
+                },
+                _ => ()
+            }
+        }
+        return Err(format!("expected ')' in expression '{}'", self.text));
+    }
+
+    fn make_call(&self, name : &str, arguments : &Vec<&str>) -> Result<Expr, String> {
+        let zone = |text : &str| parse_zone_type(text).ok_or(format!("invalid zone '{}' in expression '{}'", text, self.text));
+        let counter = |text : &str| parse_counter_type(text).ok_or(format!("invalid counter '{}' in expression '{}'", text, self.text));
+        let filter = |text : Option<&&str>| match text {
+            Some(text) => parse_card_filter(text),
+            None => Ok(CardFilter::any())
+        };
+        match (name, arguments.len()) {
+            ("count", 1) => Ok(Expr::Count(ZoneType::Battlefield, filter(arguments.first())?)),
+            ("count", 2) => Ok(Expr::Count(zone(arguments[0])?, filter(arguments.get(1))?)),
+            ("devotion", 0) => Ok(Expr::Amount(Box::new(Amount::Devotion(None)))),
+            ("devotion", 1) => match parse_color(arguments[0]) {
+                Some(color) => Ok(Expr::Amount(Box::new(Amount::Devotion(Some(color))))),
+                None => Err(format!("invalid color '{}' in expression '{}'", arguments[0], self.text))
+            },
+            ("counters", 1 | 2) => Ok(Expr::Amount(Box::new(Amount::Counters(counter(arguments[0])?, filter(arguments.get(1))?)))),
+            ("with_counters", 1 | 2) => Ok(Expr::Amount(Box::new(Amount::WithCounters(counter(arguments[0])?, filter(arguments.get(1))?)))),
+            ("random", 1) => Ok(Expr::Random(Box::new(parse(arguments[0])?))),
+            ("min", 2) => Ok(Expr::Binary(Operator::Min, Box::new(parse(arguments[0])?), Box::new(parse(arguments[1])?))),
+            ("max", 2) => Ok(Expr::Binary(Operator::Max, Box::new(parse(arguments[0])?), Box::new(parse(arguments[1])?))),
+            _ => Err(format!("unknown function '{}' with {} arguments in expression '{}'", name, arguments.len(), self.text))
+        }
+    }
+}
+
+impl Expr {
+
+    pub fn evaluate(&self, env : &dyn Environment) -> i64 {
+        match self {
+            Expr::Number(number) => *number,
+            Expr::Variable(variable) => env.variable(*variable),
+            Expr::Count(zone, filter) => env.count_in(*zone, filter),
+            Expr::Amount(amount) => env.amount(amount),
+            Expr::Random(range) => match range.evaluate(env) {
+                range if range > 0 => rand::random::<i64>().rem_euclid(range),
+                _ => 0
+            },
+            Expr::Negate(expr) => -expr.evaluate(env),
+            Expr::Not(expr) => (expr.evaluate(env) == 0) as i64,
+            Expr::Binary(Operator::And, lhs, rhs) => (lhs.is_true(env) && rhs.is_true(env)) as i64,
+            Expr::Binary(Operator::Or, lhs, rhs) => (lhs.is_true(env) || rhs.is_true(env)) as i64,
+            Expr::Binary(operator, lhs, rhs) => {
+                let (a, b) = (lhs.evaluate(env), rhs.evaluate(env));
+                match operator {
+                    Operator::Add => a + b,
+                    Operator::Subtract => a - b,
+                    Operator::Multiply => a * b,
+                    Operator::Divide => if b == 0 { 0 } else { a / b },
+                    Operator::Less => (a < b) as i64,
+                    Operator::LessOrEqual => (a <= b) as i64,
+                    Operator::Greater => (a > b) as i64,
+                    Operator::GreaterOrEqual => (a >= b) as i64,
+                    Operator::Equal => (a == b) as i64,
+                    Operator::NotEqual => (a != b) as i64,
+                    Operator::Min => a.min(b),
+                    Operator::Max => a.max(b),
+                    Operator::And | Operator::Or => unreachable!()
+                }
+            }
+        }
+    }
+
+    pub fn is_true(&self, env : &dyn Environment) -> bool {
+        return self.evaluate(env) != 0;
+    }
+}
+
+impl std::fmt::Display for Variable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Variable::HandSize => write!(f, "hand_size"),
+            Variable::LibrarySize => write!(f, "library_size"),
+            Variable::GraveyardSize => write!(f, "graveyard_size"),
+            Variable::LandsOnBattlefield => write!(f, "lands_on_battlefield"),
+            Variable::LandsPlayed => write!(f, "lands_played"),
+            Variable::ManaAvailable => write!(f, "mana_available"),
+            Variable::Life => write!(f, "life"),
+            Variable::Turn => write!(f, "turn"),
+            Variable::Opponents => write!(f, "opponents")
+        }
+    }
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Operator::Add => write!(f, "+"),
+            Operator::Subtract => write!(f, "-"),
+            Operator::Multiply => write!(f, "*"),
+            Operator::Divide => write!(f, "/"),
+            Operator::Less => write!(f, "<"),
+            Operator::LessOrEqual => write!(f, "<="),
+            Operator::Greater => write!(f, ">"),
+            Operator::GreaterOrEqual => write!(f, ">="),
+            Operator::Equal => write!(f, "=="),
+            Operator::NotEqual => write!(f, "!="),
+            Operator::And => write!(f, "&&"),
+            Operator::Or => write!(f, "||"),
+            Operator::Min => write!(f, "min"),
+            Operator::Max => write!(f, "max")
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Expr::Number(number) => write!(f, "{}", number),
+            Expr::Variable(variable) => write!(f, "{}", variable),
+            Expr::Count(zone, filter) => write!(f, "count({}, {})", zone, filter),
+            Expr::Amount(amount) => write!(f, "{}", amount),
+            Expr::Random(range) => write!(f, "random({})", range),
+            Expr::Negate(expr) => write!(f, "-{}", expr),
+            Expr::Not(expr) => write!(f, "!{}", expr),
+            Expr::Binary(operator @ (Operator::Min | Operator::Max), lhs, rhs) => write!(f, "{}({}, {})", operator, lhs, rhs),
+            Expr::Binary(operator, lhs, rhs) => write!(f, "({} {} {})", lhs, operator, rhs)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::card::CounterType;
+
+    struct TestEnvironment {}
+
+    impl Environment for TestEnvironment {
+        fn variable(&self, variable: Variable) -> i64 {
+            match variable {
+                Variable::HandSize => 4,
+                Variable::LandsOnBattlefield => 6,
+                _ => 0
+            }
+        }
+        fn count_in(&self, zone: ZoneType, filter: &CardFilter) -> i64 {
+            match (zone, filter.types.is_empty()) {
+                (ZoneType::Battlefield, false) => 3,
+                (ZoneType::Graveyard, _) => 2,
+                _ => 10
+            }
+        }
+        fn amount(&self, amount: &Amount) -> i64 {
+            match amount {
+                Amount::WithCounters(CounterType::PlusOne, _) => 2,
+                _ => 0
+            }
+        }
+    }
+
+    #[test]
+    fn test_expr_evaluate() {
+        let env = TestEnvironment {};
+        let cases = [
+            ("hand_size", 4),
+            ("lands_on_battlefield >= 5", 1),
+            ("lands_on_battlefield >= 5 && hand_size < 3", 0),
+            ("lands_on_battlefield >= 5 || hand_size < 3", 1),
+            ("1 + 2 * 3 - 4", 3),
+            ("(1 + 2) * 3", 9),
+            ("-hand_size / 2", -2),
+            ("!(hand_size == 4)", 0),
+            ("count(creature)", 3),
+            ("count(battlefield, instant/sorcery)", 3),
+            ("count(graveyard, any)", 2),
+            ("with_counters(+1/+1, creature)", 2),
+            ("min(hand_size, 2) + max(1, 3)", 5),
+            ("hand_size / 0", 0),
+            ("random(1)", 0),
+        ];
+        for (text, expected) in cases {
+            match parse(text) {
+                Ok(expr) => assert_eq!(expr.evaluate(&env), expected, "{}", text),
+                Err(error) => panic!("{}", error)
+            }
+        }
+    }
+
+    #[test]
+    fn test_expr_parse_errors() {
+        assert!(parse("hand_sizes").is_err());
+        assert!(parse("hand_size >=").is_err());
+        assert!(parse("(hand_size").is_err());
+        assert!(parse("count(battlefield, creature").is_err());
+        assert!(parse("count(nowhere, creature)").is_err());
+        assert!(parse("count(goblins)").is_err());
+        assert!(parse("hand_size 2").is_err());
+    }
+
+    #[test]
+    fn test_expr_display() {
+        let expr = parse("count(creature) >= 3 && hand_size").unwrap();
+        assert_eq!(expr.to_string(), "((count(battlefield, creature) >= 3) && hand_size)");
+        assert_eq!(parse(&expr.to_string()).unwrap(), expr);
+    }
+}
@@ -1,7 +1,10 @@
 use crate::zone::*;
 use crate::card::*;
 use crate::mana::*;
+use crate::expr::{Environment, Variable};
 use itertools::Itertools;
+use enumflags2::BitFlags;
+use rand::seq::SliceRandom;
 // use rand::Rng;
 // use rand::distributions::{Distribution, Uniform};
 
@@ -14,31 +17,191 @@ pub struct Game<'db> {
     pub command: Zone<'db>,
     pub battlefield: Zone<'db>,
     pub graveyard: Zone<'db>,
+    pub exile: Zone<'db>,
+
+    // Zone changes not yet handled by the current turn.
+    pub zone_changes: Vec<ZoneChange<'db>>,
+
+    pub life: i32,
+    // We won't pay life if it would take us below this.
+    pub min_life: i32,
+
+    // Tokens get ids from here, after the ones handed out to the deck.
+    pub next_id: u32,
+
+    pub table: Table,
+    pub interaction: Interaction,
+    pub hate: Vec<HatePiece>,
 
     pub verbose: bool,
     pub game_stats : GameStats,
 }
 
+// A card moving from one zone to another, the card is as it was when it
+// arrived in the new zone.
+#[derive(Debug, Clone)]
+pub struct ZoneChange<'db> {
+    pub card: Card<'db>,
+    pub from: ZoneType,
+    pub to: ZoneType,
+}
+
 pub enum MulliganType {
     None,
     ThreeLands
 }
 
+// How often the opponents interact with us, the chances are per turn, except
+// for counterspells which are per spell with a mana value of at least
+// 'counterspell_min_cmc'.
+#[derive(Debug, Clone)]
+pub struct Interaction {
+    pub removal: f32,               // our best creature or artifact
+    pub creature_wipe: f32,
+    pub artifact_wipe: f32,
+    pub full_wipe: f32,             // every nonland permanent
+    pub counterspell: f32,
+    pub counterspell_min_cmc: u32,
+}
+
+impl Interaction {
+    pub fn none() -> Self {
+        return Interaction {
+            removal: 0.0,
+            creature_wipe: 0.0,
+            artifact_wipe: 0.0,
+            full_wipe: 0.0,
+            counterspell: 0.0,
+            counterspell_min_cmc: 0,
+        };
+    }
+
+    pub fn casual() -> Self {
+        return Interaction {
+            removal: 0.15,
+            creature_wipe: 0.04,
+            artifact_wipe: 0.02,
+            full_wipe: 0.02,
+            counterspell: 0.05,
+            counterspell_min_cmc: 4,
+        };
+    }
+
+    pub fn competitive() -> Self {
+        return Interaction {
+            removal: 0.35,
+            creature_wipe: 0.08,
+            artifact_wipe: 0.05,
+            full_wipe: 0.03,
+            counterspell: 0.25,
+            counterspell_min_cmc: 3,
+        };
+    }
+}
+
+// The other players at the table, with how much they do on their turns, on
+// average per opponent.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub opponents: u32,
+    pub spells_per_turn: f32,
+    pub creature_deaths_per_turn: f32,
+}
+
+impl Table {
+    pub fn new(opponents: u32) -> Self {
+        return Table {
+            opponents,
+            spells_per_turn: 1.5,
+            creature_deaths_per_turn: 0.5,
+        };
+    }
+}
+
+// Static effects on the opponents' side of the table that make our deck
+// work harder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HatePiece {
+    Thalia,                         // noncreature spells cost {1} more
+    RuleOfLaw,                      // one spell each turn
+    BloodMoon,                      // nonbasic lands are mountains
+    CollectorOuphe,                 // activated abilities of artifacts can't be activated
+    ArchonOfEmeria,                 // one spell each turn, nonbasic lands enter tapped
+}
+
+pub fn parse_hate_piece(text: &str) -> Option<HatePiece> {
+    match text {
+        "thalia" => Some(HatePiece::Thalia),
+        "rule-of-law" => Some(HatePiece::RuleOfLaw),
+        "blood-moon" => Some(HatePiece::BloodMoon),
+        "collector-ouphe" => Some(HatePiece::CollectorOuphe),
+        "archon-of-emeria" => Some(HatePiece::ArchonOfEmeria),
+        _ => None
+    }
+}
+
+// Which loyalty ability to activate, the walker is always kept alive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoyaltyPolicy {
+    Grow,                           // the biggest plus ability
+    Spend,                          // the biggest minus ability
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Commander,
+    Constructed
+}
+
+impl Format {
+    pub fn starting_life(&self) -> i32 {
+        match self {
+            Format::Commander => 40,
+            Format::Constructed => 20
+        }
+    }
+}
+
 pub struct Settings {
     pub draw_card_on_turn_one: bool,
     pub turn_count: u32,
     pub mulligan : MulliganType,
+    pub max_hand_size: u32,
+    pub format: Format,
+    pub min_life: i32,
+    pub loyalty: LoyaltyPolicy,
+    pub table: Table,
+    pub interaction: Interaction,
+    pub hate: Vec<HatePiece>,
+}
+
+// What the static abilities of our permanents add up to. They last as long
+// as the permanents stay on the battlefield, so these are recomputed at the
+// start of each turn and whenever a permanent enters or leaves.
+#[derive(Debug, Default)]
+struct StaticEffects {
+    land_limit: u32,                // on top of the regular land drop
+    no_maximum_hand_size: bool,
+    lands_from_graveyard: bool,
+    lands_from_library_top: bool,
 }
 
 struct Turn<'db, 'game> {
     game: &'game mut Game<'db>,
     turn_number: u32,
     lands_played : u32,
-    land_limit: u32,
+    land_limit: u32,                // this turn only, see land_limit() for the total
+    statics: StaticEffects,
     mana_pool : ManaPool,
     mana_spent : ManaPool,
     turn_stats : TurnStats,
-    cards_in_mana_pool: std::collections::HashSet<u32>
+    cards_in_mana_pool: std::collections::HashSet<u32>,
+    loyalty_activated: std::collections::HashSet<u32>,
+    phase: Phase,
+    events: Vec<(Phase, String)>,
+    lands_entered: u32,
+    spells_cast: u32,
+    counterspell_held: bool,        // by the opponents for one of our spells this turn
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +214,12 @@ pub struct TurnStats {
     pub cards_in_hand: u32,
     pub mana_available: u32,
     pub mana_spent: u32,
+    pub cards_discarded: u32,
+    pub cards_cast_free: u32,
+    pub life_paid: u32,
+    pub permanents_removed: u32,
+    pub spells_countered: u32,
+    pub cards_drawn_by: std::collections::HashMap<String, u32>,
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +227,7 @@ pub struct GameStats {
     pub game_number: u32,
     pub mulligan_count: u32,
     pub turn_commander_played : u32,
+    pub commander_casts : u32,
     pub turns_stats : Vec<TurnStats>,
     pub out_of_cards : bool,
 }
@@ -70,11 +240,20 @@ impl<'db> Game<'db> {
             command: Zone::new("Command"),
             battlefield: Zone::new("Battlefield"),
             graveyard: Zone::new("Graveyard"),
+            exile: Zone::new("Exile"),
+            zone_changes: Vec::new(),
+            life: Format::Commander.starting_life(),
+            min_life: 0,
+            next_id: 1,
+            table: Table::new(3),
+            interaction: Interaction::none(),
+            hate: Vec::new(),
             verbose: false,
             game_stats : GameStats {
                 game_number: 0,
                 mulligan_count: 0,
                 turn_commander_played: 0,
+                commander_casts: 0,
                 turns_stats: Vec::new(),
                 out_of_cards: false
             },
@@ -88,6 +267,69 @@ impl<'db> Game<'db> {
         self.command.dump();
     }
 
+    pub fn zone(&self, zone: ZoneType) -> &Zone<'db> {
+        match zone {
+            ZoneType::Library => &self.library,
+            ZoneType::Hand => &self.hand,
+            ZoneType::Battlefield => &self.battlefield,
+            ZoneType::Graveyard => &self.graveyard,
+            ZoneType::Exile => &self.exile,
+            ZoneType::Command => &self.command,
+        }
+    }
+
+    pub fn zone_mut(&mut self, zone: ZoneType) -> &mut Zone<'db> {
+        match zone {
+            ZoneType::Library => &mut self.library,
+            ZoneType::Hand => &mut self.hand,
+            ZoneType::Battlefield => &mut self.battlefield,
+            ZoneType::Graveyard => &mut self.graveyard,
+            ZoneType::Exile => &mut self.exile,
+            ZoneType::Command => &mut self.command,
+        }
+    }
+
+    // Puts a card, which has already been taken out of its zone, into zone
+    // 'to'. Cards going to the library are put on top. All movement of cards
+    // between zones goes through here, or move_card_to_bottom(), so it can
+    // be recorded as a zone change.
+    pub fn move_card(&mut self, card: Card<'db>, to: ZoneType) {
+        // We always choose to put the commander back in the command zone.
+        let to = match to {
+            ZoneType::Graveyard | ZoneType::Exile if card.commander => ZoneType::Command,
+            _ => to
+        };
+        let card = self.record_zone_change(card, to);
+        if card.is_token() && to != ZoneType::Battlefield {
+            return;
+        }
+        match to {
+            ZoneType::Library => self.library.put_on_top(card),
+            _ => self.zone_mut(to).add(card)
+        }
+    }
+
+    pub fn move_card_to_bottom(&mut self, card: Card<'db>) {
+        let card = self.record_zone_change(card, ZoneType::Library);
+        self.library.put_on_bottom(card);
+    }
+
+    fn record_zone_change(&mut self, mut card: Card<'db>, to: ZoneType) -> Card<'db> {
+        let from = card.zone;
+        card.zone = to;
+        if from != to {
+            // A card in a new zone is a new object, without its counters.
+            card.counters.clear();
+            if to == ZoneType::Battlefield {
+                if let Some(loyalty) = card.data.loyalty {
+                    card.add_counters(CounterType::Loyalty, loyalty);
+                }
+            }
+            self.zone_changes.push(ZoneChange { card: card.clone(), from, to });
+        }
+        return card;
+    }
+
     pub fn draw_cards(&mut self, count : u32) {
         for _i in 0..count {
             match self.library.draw() {
@@ -95,7 +337,7 @@ impl<'db> Game<'db> {
                     if self.verbose {
                         println!(" - draw card: {}", card);
                     }
-                    self.hand.add(card);
+                    self.move_card(card, ZoneType::Hand);
                 },
                 None => self.game_stats.out_of_cards = true
             }
@@ -169,14 +411,24 @@ impl<'db> Game<'db> {
         assert_eq!(self.hand.size(), 0);
         assert_eq!(self.battlefield.size(), 0);
         assert_eq!(self.graveyard.size(), 0);
+        assert_eq!(self.exile.size(), 0);
 
         self.command.sort_by_cmc();
 
         let id = self.command.assign_ids(1);
-        self.library.assign_ids(id);
+        self.next_id = self.library.assign_ids(id);
+
+        self.life = settings.format.starting_life();
+        self.min_life = settings.min_life;
+        self.table = settings.table.clone();
+        self.interaction = settings.interaction.clone();
+        self.hate = settings.hate.clone();
 
         self.draw_and_mulligan(settings);
 
+        // The opening hand doesn't trigger anything.
+        self.zone_changes.clear();
+
         for i in 0..settings.turn_count {
 
             let turn_stats;
@@ -191,6 +443,41 @@ impl<'db> Game<'db> {
     }
 }
 
+impl<'db, 'game> Environment for Turn<'db, 'game> {
+    fn variable(&self, variable: Variable) -> i64 {
+        match variable {
+            Variable::HandSize => self.game.hand.size() as i64,
+            Variable::LibrarySize => self.game.library.size() as i64,
+            Variable::GraveyardSize => self.game.graveyard.size() as i64,
+            Variable::LandsOnBattlefield => self.game.battlefield.cards.iter().filter(|c| c.is_type(Types::Land)).count() as i64,
+            Variable::LandsPlayed => self.lands_played as i64,
+            Variable::ManaAvailable => self.mana_pool.cmc() as i64 - self.mana_spent.cmc() as i64,
+            Variable::Life => self.game.life as i64,
+            Variable::Turn => self.turn_number as i64,
+            Variable::Opponents => self.game.table.opponents as i64,
+        }
+    }
+
+    fn count_in(&self, zone: ZoneType, filter: &CardFilter) -> i64 {
+        return self.game.zone(zone).cards.iter().filter(|card| filter.matches(card.data)).count() as i64;
+    }
+
+    fn amount(&self, amount: &Amount) -> i64 {
+        return self.count(amount) as i64;
+    }
+}
+
+fn choose_loyalty_ability<'db>(walker: &Card<'db>, policy: LoyaltyPolicy) -> Option<&'db Ability> {
+    let abilities = walker.data.abilities.iter().flatten()
+        .filter_map(|ability| ability.cost.is_loyalty().map(|loyalty| (ability, loyalty)))
+        .filter(|(_, loyalty)| walker.counters(CounterType::Loyalty) as i32 + loyalty > 0);
+    let chosen = match policy {
+        LoyaltyPolicy::Grow => abilities.max_by_key(|(_, loyalty)| *loyalty),
+        LoyaltyPolicy::Spend => abilities.min_by_key(|(_, loyalty)| *loyalty),
+    };
+    return chosen.map(|(ability, _)| ability);
+}
+
 impl<'db, 'game> Turn<'db, 'game> {
 
     pub fn new(game : &'game mut Game<'db>, turn : u32) -> Self {
@@ -199,6 +486,7 @@ impl<'db, 'game> Turn<'db, 'game> {
             turn_number: turn,
             lands_played: 0,
             land_limit: 1,
+            statics: StaticEffects::default(),
             mana_pool: ManaPool::new(),
             mana_spent: ManaPool::new(),
             turn_stats: TurnStats {
@@ -209,9 +497,21 @@ impl<'db, 'game> Turn<'db, 'game> {
                 cards_played: 0,
                 cards_in_hand: 0,
                 mana_available: 0,
-                mana_spent: 0
+                mana_spent: 0,
+                cards_discarded: 0,
+                cards_cast_free: 0,
+                life_paid: 0,
+                permanents_removed: 0,
+                spells_countered: 0,
+                cards_drawn_by: std::collections::HashMap::new()
             },
-            cards_in_mana_pool: std::collections::HashSet::new()
+            cards_in_mana_pool: std::collections::HashSet::new(),
+            loyalty_activated: std::collections::HashSet::new(),
+            phase: Phase::Untap,
+            events: Vec::new(),
+            lands_entered: 0,
+            spells_cast: 0,
+            counterspell_held: false,
         }
     }
 
@@ -220,13 +520,135 @@ impl<'db, 'game> Turn<'db, 'game> {
             println!("\n********** Turn #{} **********", self.turn_number);
         }
 
+        self.opponents_play();
+        self.opponents_interact();
+        self.update_static_effects();
+
+        for phase in PHASES {
+            self.begin_phase(phase);
+            // "At the beginning of" abilities trigger once the untap and
+            // the draw are done, and before anything else.
+            match phase {
+                Phase::Untap => {
+                    self.untap_step();
+                    self.trigger_phase_abilities();
+                },
+                Phase::Draw => {
+                    self.draw_step(settings);
+                    self.trigger_phase_abilities();
+                },
+                Phase::FirstMain | Phase::SecondMain => {
+                    self.trigger_phase_abilities();
+                    self.main_phase(settings);
+                },
+                Phase::Upkeep | Phase::Combat | Phase::End => self.trigger_phase_abilities(),
+                Phase::Cleanup => {
+                    self.trigger_phase_abilities();
+                    self.cleanup_step(settings);
+                }
+            }
+        }
+
+        if self.game.verbose {
+            println!("Mana available: {} ({})", self.mana_pool, self.mana_pool.cmc());
+            println!("Mana spent: {} ({})", self.mana_spent, self.mana_spent.cmc());
+            self.game.hand.dump();
+            self.game.battlefield.sort();
+            self.game.battlefield.dump();
+            println!("Events:");
+            for (phase, event) in &self.events {
+                println!("   {:<10} {}", format!("{}:", phase), event);
+            }
+            println!();
+        }
+
+        self.turn_stats.lands_cheated = self.lands_entered - self.turn_stats.lands_played;
+        self.turn_stats.mana_available = self.mana_pool.cmc();
+        self.turn_stats.mana_spent = self.mana_spent.cmc();
+        self.turn_stats.cards_in_hand = self.game.hand.size();
+    }
+
+    fn begin_phase(&mut self, phase: Phase) {
+        self.phase = phase;
+        if self.game.verbose {
+            println!("--- {} ---", phase);
+        }
+    }
+
+    // Records an event in the current phase, listed at the end of the turn
+    // in the verbose log.
+    fn record_event(&mut self, event: String) {
+        self.events.push((self.phase, event));
+    }
+
+    // Puts the card into zone 'to' and handles the resulting zone change.
+    fn move_card(&mut self, card: Card<'db>, to: ZoneType) {
+        self.game.move_card(card, to);
+        self.handle_zone_changes();
+    }
+
+    // Updates statistics and resolves triggers, like landfall, for the
+    // zone changes that have happened since the last time.
+    fn handle_zone_changes(&mut self) {
+        while !self.game.zone_changes.is_empty() {
+            let change = self.game.zone_changes.remove(0);
+            if change.from == ZoneType::Battlefield || change.to == ZoneType::Battlefield {
+                self.update_static_effects();
+            }
+            if change.to == ZoneType::Battlefield && change.card.is_type(Types::Land) {
+                self.lands_entered += 1;
+            }
+            for (card, ability) in self.find_active_abilities(|ability| {
+                ability.trigger.is_zone_change(change.from, change.to, change.card.data)
+                && (ability.availability == 1.0 || rand::random::<f32>() < ability.availability)
+            }) {
+                if self.game.verbose {
+                    println!(" - {} triggers on {} moving {} -> {}", card, change.card.data.name, change.from, change.to);
+                }
+                self.record_event(format!("{} :: {}", card.data.name, ability));
+                self.resolve_effect(&card, &ability.effect);
+            }
+        }
+    }
+
+    fn update_static_effects(&mut self) {
+        let mut statics = StaticEffects::default();
+        for (_, ability) in self.find_active_abilities(|ability| ability.trigger.is_static()) {
+            match &ability.effect {
+                Effect::LandLimit(increase) => statics.land_limit += increase,
+                Effect::NoMaximumHandSize => statics.no_maximum_hand_size = true,
+                Effect::PlayLandsFromGraveyard => statics.lands_from_graveyard = true,
+                Effect::PlayLandsFromLibraryTop => statics.lands_from_library_top = true,
+                _ => ()
+            }
+        }
+        self.statics = statics;
+    }
+
+    fn land_limit(&self) -> u32 {
+        return self.land_limit + self.statics.land_limit;
+    }
+
+    fn untap_step(&mut self) {
         self.game.battlefield.untap_all();
 
-        // gather mana pool from lands, rocks and dorks
+        // nonbasic lands only tap for red under 'Blood Moon'
+        let mountains : Vec<Card<'db>> = self.game.battlefield.cards.iter()
+            .filter(|card| !card.tapped && self.is_mountain(card))
+            .cloned()
+            .collect();
+        for card in mountains {
+            self.add_to_mana_pool(&card, &ManaPool::new_from_single(&RED));
+        }
+
+        // gather mana pool from lands, rocks and dorks, mana converters like
+        // signets and 'Horizon Canopy' are used when paying, see
+        // convert_mana_for()
         for (card, ability) in self.find_abilities_on_battlefield(|ability| {
             ability.trigger.is_activated()
             && ability.cost.is_tap()
             && ability.effect.is_produce_mana()
+            && !ability.is_mana_converter()
             && (ability.availability == 1.0 || rand::random::<f32>() < ability.availability)
         }) {
             if self.cards_in_mana_pool.contains(&card.id) {
@@ -238,79 +660,172 @@ impl<'db, 'game> Turn<'db, 'game> {
                 _ => panic!("invalid effect when build mana pool...")
             }
         }
-        if self.game.verbose {
-            println!(" - available mana: {} ({})", self.mana_pool, self.mana_pool.cmc());
-            println!();
-        }
 
-        // other upkeep stuff
+        // ... then the ones depending on the board, some of which need mana
+        // from the above, like 'Cabal Coffers'.
         for (card, ability) in self.find_abilities_on_battlefield(|ability| {
-            ability.trigger.is_upkeep()
-            && ability.cost.is_none()
-            && (ability.availability == 1.0 || rand::random::<f32>() < ability.availability)
+            ability.trigger.is_activated()
+            && ability.cost.is_tap()
+            && ability.effect.is_produce_mana_per()
         }) {
+            if self.cards_in_mana_pool.contains(&card.id) {
+                continue;
+            }
             match &ability.effect {
-                Effect::ProduceMana(mana) => self.add_to_mana_pool(&card, mana),
-                Effect::FetchLand{ to_hand: hand, to_battlefield: bf } => self.fetch_lands(hand, bf),
-                Effect::Draw(ratios) => self.draw_cards(&card, ratios),
-                _ => ()
+                Effect::ProduceManaPer { mana, amount } => self.add_scaled_to_mana_pool(&card, mana, amount, &ability.cost),
+                _ => panic!("invalid effect when build mana pool...")
             }
         }
+        if self.game.verbose {
+            println!(" - available mana: {} ({})", self.mana_pool, self.mana_pool.cmc());
+        }
+    }
 
-        // draw card for turn..
+    fn draw_step(&mut self, settings: &Settings) {
         if self.turn_number > 1 || settings.draw_card_on_turn_one {
             if self.game.verbose {
                 println!(" - drawing card for turn:");
             }
             self.game.draw_cards(1);
             self.turn_stats.cards_drawn += 1;
+            self.record_event("drew card for turn".to_string());
+            self.handle_zone_changes();
         }
+    }
 
-        if self.game.verbose {
-            println!("");
-        }
+    fn main_phase(&mut self, settings: &Settings) {
         while self.try_to_play_land()
+            || self.try_to_landcycle()
             || self.try_to_play_commander()
+            || self.try_to_play_planeswalker()
+            || self.try_to_activate_loyalty_ability(settings.loyalty)
             || self.try_to_activate_ramp_ability()
             || self.try_to_play_ramp_spell()
+            || self.try_to_play_tutor_spell()
+            || self.try_to_play_recursion_spell()
+            || self.try_to_channel()
             || ((self.game.hand.size() <= 5 && self.game.library.size() > 10)
                 && (self.try_to_activate_draw_ability() || self.try_to_play_draw_spell()))
             || self.try_to_empty_hand()
+            || self.try_to_cycle()
+            || self.try_to_activate_counters_ability()
+            || self.try_to_cast_from_graveyard()
             {
             if self.game.verbose {
                 println!("");
             }
             continue;
         }
+    }
 
-        if self.game.verbose {
-            println!("Mana available: {} ({})", self.mana_pool, self.mana_pool.cmc());
-            println!("Mana spent: {} ({})", self.mana_spent, self.mana_spent.cmc());
-            self.game.hand.dump();
-            self.game.battlefield.sort();
-            self.game.battlefield.dump();
-            println!();
+    fn cleanup_step(&mut self, settings: &Settings) {
+        if self.statics.no_maximum_hand_size || self.game.hand.size() <= settings.max_hand_size {
+            return;
         }
 
-        self.turn_stats.mana_available = self.mana_pool.cmc();
-        self.turn_stats.mana_spent = self.mana_spent.cmc();
-        self.turn_stats.cards_in_hand = self.game.hand.size();
+        let excess = self.game.hand.size() - settings.max_hand_size;
+        for card in self.choose_discards(excess) {
+            let card = self.game.hand.take(card.id).unwrap();
+            if self.game.verbose {
+                println!(" - discarding {} -> graveyard!", card);
+            }
+            self.record_event(format!("discarded {}", card.data.name));
+            self.move_card(card, ZoneType::Graveyard);
+            self.turn_stats.cards_discarded += 1;
+        }
+    }
+
+    // Lands beyond what we need for next turn's land drops go first, then
+    // the most expensive spells we can't cast next turn, then the most
+    // expensive of the rest.
+    fn choose_discards(&self, count: u32) -> Vec<Card<'db>> {
+        let mut lands = self.game.hand.query(Types::Land);
+        let mut spells : Vec<Card<'db>> = self.game.hand.cards.iter().filter(|c| !c.is_type(Types::Land)).cloned().collect();
+        sort_cards_on_colors_produced(&mut lands);
+        spells.sort_by(|a, b| b.data.cmc.cmp(&a.data.cmc));
+
+        let mana_next_turn = self.mana_pool.cmc() + 1;
+        let land_drops = self.land_limit() as usize;
+        let excess_lands = lands.len().saturating_sub(land_drops);
+
+        let mut discards : Vec<Card<'db>> = Vec::new();
+        discards.extend(lands.iter().rev().take(excess_lands).cloned());
+        discards.extend(spells.iter().filter(|c| self.cost_cmc(c) > mana_next_turn).cloned());
+        discards.extend(spells.iter().filter(|c| self.cost_cmc(c) <= mana_next_turn).cloned());
+        discards.extend(lands.iter().take(land_drops.min(lands.len())).rev().cloned());
+        discards.truncate(count as usize);
+        return discards;
+    }
+
+    // Resolves "at the beginning of ..." abilities for the current phase.
+    fn trigger_phase_abilities(&mut self) {
+        let phase = self.phase;
+        for (card, ability) in self.find_active_abilities(|ability| {
+            ability.trigger.is_beginning_of(phase)
+            && matches!(ability.cost, Cost::None | Cost::Life(_))
+            && (ability.availability == 1.0 || rand::random::<f32>() < ability.availability)
+        }) {
+            // Optional life payments, like 'Sylvan Library'. An earlier
+            // payment may have left too little life for this one.
+            if let Some(life) = ability.cost.is_life() {
+                if !self.can_pay_life(life) {
+                    continue;
+                }
+                self.pay_life(&card, life);
+            }
+            self.record_event(format!("{} :: {}", card.data.name, ability));
+            self.resolve_effect(&card, &ability.effect);
+        }
     }
 
+    // Finds the abilities of our permanents, and of cards in the command zone
+    // and in the graveyard that work from there, like eminence, that we can
+    // afford to use. Abilities working from hand are card specific, see
+    // find_abilities_in_hand().
+    fn find_active_abilities<F>(&self, selector: F) -> Vec<(Card<'db>, &'db Ability)> where F: Fn(&Ability) -> bool {
+        return self.find_abilities_in(ZoneType::Battlefield | ZoneType::Command | ZoneType::Graveyard, selector);
+    }
+
+    // Like find_active_abilities(), for abilities that tap or move the
+    // permanent, like mana abilities and sacrifice outlets.
     fn find_abilities_on_battlefield<F>(&self, selector: F) -> Vec<(Card<'db>, &'db Ability)> where F: Fn(&Ability) -> bool {
+        return self.find_abilities_in(ZoneType::Battlefield.into(), selector);
+    }
+
+    fn find_abilities_in<F>(&self, zones: BitFlags<ZoneType>, selector: F) -> Vec<(Card<'db>, &'db Ability)> where F: Fn(&Ability) -> bool {
 
         let mut result : Vec<(Card, &Ability)> = Vec::new();
-        for card in &self.game.battlefield.cards {
+        let cards = zones
+            .iter()
+            .flat_map(|zone| self.game.zone(zone).cards.iter().map(move |card| (zone, card)));
+        for (zone, card) in cards {
             for ability in card.data.abilities.iter().flatten() {
-                if !selector(ability) {
+                if !ability.works_from(zone) || !selector(ability) || !self.condition_holds(ability) {
+                    continue;
+                }
+
+                let on_battlefield = zone == ZoneType::Battlefield;
+                if on_battlefield && (self.is_mountain(card) || (ability.trigger.is_activated() && self.is_ouphed(card))) {
                     continue;
                 }
 
-                // If it requires tapping, skip if we're already tapped.
-                if ability.cost.is_tap() && card.tapped {
+                // Loyalty abilities are only activated through the policy.
+                if ability.cost.is_loyalty().is_some() {
                     continue;
                 }
 
+                // If it requires tapping, skip if we're already tapped or
+                // if it is a creature that just arrived.
+                if ability.cost.is_tap() && (!on_battlefield || card.tapped || card.is_summoning_sick(self.turn_number)) {
+                    continue;
+                }
+
+                if let Some(life) = ability.cost.is_life() {
+                    if !self.can_pay_life(life) {
+                        continue;
+                    }
+                }
+
                 // Finally, check the mana cost. A little gotcha here is that
                 // some cards are both tapped as activated abilities and
                 // tapped for mana, so for these cards, we've already added
@@ -324,7 +839,7 @@ impl<'db, 'game> Turn<'db, 'game> {
                         }
                     }
 
-                    if mana_pool.can_also_pay_for(&self.mana_spent, &ability_cost) == None {
+                    if !self.can_afford_from(&mana_pool, ability_cost) {
                         continue;
                     }
                 }
@@ -336,19 +851,47 @@ impl<'db, 'game> Turn<'db, 'game> {
         return result;
     }
 
+    // Finds abilities that are activated from hand, like cycling, that we
+    // can afford to activate.
+    fn find_abilities_in_hand<F>(&self, selector: F) -> Vec<(Card<'db>, &'db Ability)> where F: Fn(&Card<'db>, &Ability) -> bool {
+        let mut result : Vec<(Card, &Ability)> = Vec::new();
+        for card in &self.game.hand.cards {
+            for ability in card.data.abilities.iter().flatten() {
+                if !ability.works_from(ZoneType::Hand) || !ability.trigger.is_activated() || !selector(card, ability) || !self.condition_holds(ability) {
+                    continue;
+                }
+                if let Some(ability_cost) = ability.cost.is_mana() {
+                    if !self.can_afford(ability_cost) {
+                        continue;
+                    }
+                }
+                if let Some(life) = ability.cost.is_life() {
+                    if !self.can_pay_life(life) {
+                        continue;
+                    }
+                }
+                result.push((card.clone(), ability));
+            }
+        }
+        return result;
+    }
+
     pub fn find_spells_in_hand<F>(&self, selector: F) -> Vec<Card<'db>> where F : Fn(&Ability) -> bool {
         let mut result: Vec<Card> = Vec::new();
+        if !self.can_cast_spell() {
+            return result;
+        }
         for card in &self.game.hand.cards {
             for ability in card.data.abilities.iter().flatten() {
-                if card.is_type(Types::Land) {
+                if card.is_type(Types::Land) || !ability.works_from(ZoneType::Battlefield) {
                     continue;
                 }
 
-                if !selector(ability) {
+                if !selector(ability) || !self.condition_holds(ability) {
                     continue;
                 }
                 if let Some(casting_cost) = &card.data.mana_cost {
-                    if self.mana_pool.can_also_pay_for(&self.mana_spent, &casting_cost) == None {
+                    if !self.can_afford(&self.cost_of(card, casting_cost)) {
                         continue;
                     }
                 }
@@ -358,42 +901,125 @@ impl<'db, 'game> Turn<'db, 'game> {
         return result;
     }
 
+    // Planeswalkers are cast as soon as we can, the biggest one first, to
+    // have their loyalty abilities every turn after.
+    fn try_to_play_planeswalker(&mut self) -> bool {
+        let mut candidates : Vec<Card<'db>> = self.find_spells_in_hand(|ability| ability.cost.is_loyalty().is_some())
+            .into_iter()
+            .filter(|card| card.is_type(Types::Planeswalker))
+            .collect();
+        if candidates.is_empty() {
+            return false;
+        }
+        candidates.sort_by(|a, b| b.data.cmc.cmp(&a.data.cmc));
+        if self.game.verbose {
+            for card in &candidates {
+                println!(" - planeswalker candidate: {}", card);
+            }
+        }
+        let card = self.game.hand.take(candidates[0].id).unwrap();
+        self.play_card(card);
+        return true;
+    }
+
+    // Activates one loyalty ability of a planeswalker that hasn't been
+    // activated yet this turn.
+    fn try_to_activate_loyalty_ability(&mut self, policy: LoyaltyPolicy) -> bool {
+        let walkers : Vec<Card<'db>> = self.game.battlefield.query(Types::Planeswalker)
+            .into_iter()
+            .filter(|walker| !self.loyalty_activated.contains(&walker.id))
+            .collect();
+        for walker in walkers {
+            self.loyalty_activated.insert(walker.id);
+            let ability = match choose_loyalty_ability(&walker, policy) {
+                Some(ability) => ability,
+                None => continue
+            };
+            if self.game.verbose {
+                println!(" - activating {} :: {}", walker, ability);
+            }
+            self.record_event(format!("{} :: {}", walker.data.name, ability));
+
+            let mut walker = self.game.battlefield.take(walker.id).unwrap();
+            let loyalty = ability.cost.is_loyalty().unwrap();
+            if loyalty > 0 {
+                walker.add_counters(CounterType::Loyalty, loyalty as u32);
+            } else {
+                walker.remove_counters(CounterType::Loyalty, loyalty.unsigned_abs());
+            }
+            self.game.battlefield.add(walker.clone());
+            self.resolve_effect(&walker, &ability.effect);
+            return true;
+        }
+        return false;
+    }
+
     pub fn try_to_play_commander(&mut self) -> bool {
-        if self.game.command.size() == 0 {
+        if self.game.command.size() == 0 || !self.can_cast_spell() {
             return false;
         }
-        let commander = &self.game.command.cards[0];
-        let commander_cost = commander.data.mana_cost.as_ref().expect("commander has no mana cost!!!");
-        match self.mana_pool.can_also_pay_for(&self.mana_spent, &commander_cost) {
-            Some(spent) => {
-                if self.game.verbose {
-                    println!(" - playing commander, {}", commander);
-                    println!("   -> to battlefield");
-                }
-                self.game.game_stats.turn_commander_played = self.turn_number;
-                self.turn_stats.cards_played += 1;
-                self.mana_spent = spent;
-                let card = self.game.command.take(commander.id).expect("commander wasn't there!!!");
-                self.game.battlefield.add(card);
-                return true;
-            },
-            None => return false
+        let commander = self.game.command.cards[0].clone();
+        let mut commander_cost = commander.data.mana_cost.clone().expect("commander has no mana cost!!!");
+        for _ in 0..2 * self.game.game_stats.commander_casts {
+            commander_cost.add_mana(&COLORLESS);
+        }
+        let commander_cost = self.cost_of(&commander, &commander_cost);
+        if !self.pay_mana(&commander_cost) {
+            return false;
+        }
+        if self.game.verbose {
+            println!(" - playing commander, {} for {}", commander, commander_cost);
+            println!("   -> to battlefield");
+        }
+        if self.game.game_stats.turn_commander_played == 0 {
+            self.game.game_stats.turn_commander_played = self.turn_number;
+        }
+        self.game.game_stats.commander_casts += 1;
+        self.spells_cast += 1;
+        self.turn_stats.cards_played += 1;
+        let mut card = self.game.command.take(commander.id).expect("commander wasn't there!!!");
+        card.entered_battlefield = self.turn_number;
+        self.record_event(format!("cast commander {}", card.data.name));
+        if self.is_countered(&card) {
+            self.move_card(card, ZoneType::Graveyard);
+            return true;
         }
+        self.trigger_spell_cast(&card);
+        self.move_card(card, ZoneType::Battlefield);
+        return true;
     }
 
     pub fn try_to_play_land(&mut self) -> bool {
 
-        if self.lands_played >= self.land_limit {
+        if self.lands_played >= self.land_limit() {
             return false;
         }
 
-        let mut lands_in_hand = self.game.hand.query(Types::Land);
+        // A land on top of the library is a free card, so it goes first.
+        if self.statics.lands_from_library_top {
+            if let Some(top) = self.game.library.cards.last().filter(|card| card.is_type(Types::Land)) {
+                if self.game.verbose {
+                    println!(" - playing land from top of library");
+                }
+                let card = self.take_playable_land(top.id);
+                self.play_card(card);
+                return true;
+            }
+        }
+
+        // Lands in the graveyard go first, so that they are preferred over
+        // equally good lands in hand.
+        let mut lands_in_hand = match self.statics.lands_from_graveyard {
+            true => self.game.graveyard.query(Types::Land),
+            false => Vec::new()
+        };
+        lands_in_hand.extend(self.game.hand.query(Types::Land));
         if lands_in_hand.len() == 0 {
             return false;
         }
 
         if self.game.verbose {
-            println!(" - trying to play lands, {} in hand", lands_in_hand.len());
+            println!(" - trying to play lands, {} available", lands_in_hand.len());
         }
 
         // Check if one or more of the lands have a playing cost that we won't
@@ -423,7 +1049,7 @@ impl<'db, 'game> Turn<'db, 'game> {
                 for land in &lands_in_hand {
                     match &land.data.produced_mana {
                         Some(mana) => if mana.contains(color) {
-                            let card = self.game.hand.take(land.id).unwrap();
+                            let card = self.take_playable_land(land.id);
                             self.play_card(card);
                             return true;
                         },
@@ -437,24 +1063,211 @@ impl<'db, 'game> Turn<'db, 'game> {
             println!(" - no match for preference...");
         }
 
-        let card = self.game.hand.take(lands_in_hand[0].id).unwrap();
+        let card = self.take_playable_land(lands_in_hand[0].id);
         self.play_card(card);
         return true;
     }
 
-    fn try_to_activate_ramp_ability(&mut self) -> bool {
-        let mut abilities = self.find_abilities_on_battlefield(|ability| {
-            ability.trigger.is_activated()
-            && ability.effect.is_fetch_land()
-            && ability.availability >= rand::random::<f32>()
-        });
+    fn take_playable_land(&mut self, id: u32) -> Card<'db> {
+        if let Some(card) = self.game.hand.take(id) {
+            return card;
+        }
+        if let Some(card) = self.game.graveyard.take(id) {
+            return card;
+        }
+        return self.game.library.take(id).expect("land is neither in hand, graveyard nor library!!!");
+    }
+
+    // Landcycles, like 'Ash Barrens', when we have no land to make this
+    // turn's land drop with.
+    fn try_to_landcycle(&mut self) -> bool {
+        if self.lands_played >= self.land_limit() || self.game.hand.cards.iter().any(|card| card.is_type(Types::Land)) {
+            return false;
+        }
+        let mut abilities = self.find_abilities_in_hand(|_, ability| ability.effect.is_fetch_land());
         if abilities.is_empty() {
             return false;
         }
-        abilities.sort_by(|(_, ability_a), (_, ability_b)| {
-            let cost_a = ability_a.cost.is_mana().map_or(0, |cost| cost.cmc());
-            let cost_b = ability_b.cost.is_mana().map_or(0, |cost| cost.cmc());
-            cost_a.cmp(&cost_b)
+        abilities.sort_by_key(|(_, ability)| ability.cost.is_mana().map_or(0, |cost| cost.cmc()));
+        let (card, ability) = abilities.remove(0);
+        self.activate_from_hand(card, ability);
+        return true;
+    }
+
+    // Spends what's left on abilities like 'Contagion Engine', as long as
+    // there are counters on the battlefield to work with.
+    fn try_to_activate_counters_ability(&mut self) -> bool {
+        let has_counters = self.game.battlefield.cards.iter().any(|card| !card.counters.is_empty());
+        let abilities = self.find_abilities_on_battlefield(|ability| {
+            ability.trigger.is_activated()
+            && ability.effect.is_counters()
+            && !ability.cost.is_none()
+            && (has_counters || ability.effect.is_add_counters())
+            && ability.availability >= rand::random::<f32>()
+        });
+        let (card, ability) = match abilities.first() {
+            Some(found) => found.clone(),
+            None => return false
+        };
+        if self.game.verbose {
+            println!(" - activating {} :: {}", card, ability);
+        }
+        self.record_event(format!("{} :: {}", card.data.name, ability));
+        let card = self.game.battlefield.take(card.id).unwrap();
+        self.pay_activation_cost(card.clone(), &ability.cost);
+        self.resolve_effect(&card, &ability.effect);
+        return true;
+    }
+
+    // The opponents' turns, before our untap step. Each of them draws, casts
+    // spells and has creatures die, triggering abilities like 'Smothering
+    // Tithe' or 'Black Market'.
+    fn opponents_play(&mut self) {
+        let table = self.game.table.clone();
+        let draw_step_abilities = self.find_active_abilities(|ability| ability.trigger == Trigger::EachDrawStep);
+        for _ in 0..table.opponents {
+            // their regular draw, and the extra cards of 'Howling Mine' and the like
+            let draws : u32 = 1 + draw_step_abilities.iter()
+                .map(|(_, ability)| match &ability.effect {
+                    Effect::Draw(ratios) => ratios[rand::random::<usize>() % ratios.len()],
+                    _ => 0
+                })
+                .sum::<u32>();
+            for _ in 0..draws {
+                self.trigger_table_event(TableEvent::Draw);
+            }
+            for i in 0..occurrences(table.spells_per_turn) {
+                self.trigger_table_event(TableEvent::SpellCast);
+                if i == 1 {
+                    self.trigger_table_event(TableEvent::SecondSpellCast);
+                }
+            }
+            for _ in 0..occurrences(table.creature_deaths_per_turn) {
+                self.trigger_table_event(TableEvent::CreatureDies);
+            }
+        }
+    }
+
+    fn trigger_table_event(&mut self, event: TableEvent) {
+        for (card, ability) in self.find_active_abilities(|ability| {
+            ability.trigger.is_opponent(event)
+            && (ability.availability == 1.0 || rand::random::<f32>() < ability.availability)
+        }) {
+            if self.game.verbose {
+                println!(" - {} triggers on {}", card, event);
+            }
+            self.record_event(format!("{} :: {}", card.data.name, ability));
+            self.resolve_effect(&card, &ability.effect);
+        }
+    }
+
+    // The opponents' turns, before our untap step. They may remove our best
+    // creature or artifact, and wipe the board.
+    fn opponents_interact(&mut self) {
+        let interaction = self.game.interaction.clone();
+        let removal_target = self.game.battlefield.cards.iter()
+            .filter(|card| !card.is_type(Types::Land) && (card.is_type(Types::Creature) || card.is_type(Types::Artifact)))
+            .max_by_key(|card| card.data.cmc)
+            .map(|card| card.id);
+        if let Some(id) = removal_target {
+            if rand::random::<f32>() < interaction.removal {
+                self.remove_permanents("removal", |card| card.id == id);
+            }
+        }
+        if rand::random::<f32>() < interaction.creature_wipe {
+            self.remove_permanents("creature wipe", |card| card.is_type(Types::Creature));
+        }
+        if rand::random::<f32>() < interaction.artifact_wipe {
+            self.remove_permanents("artifact wipe", |card| card.is_type(Types::Artifact) && !card.is_type(Types::Land));
+        }
+        if rand::random::<f32>() < interaction.full_wipe {
+            self.remove_permanents("board wipe", |card| !card.is_type(Types::Land));
+        }
+        self.counterspell_held = rand::random::<f32>() < interaction.counterspell;
+    }
+
+    fn remove_permanents<F>(&mut self, reason: &str, selector: F) where F: Fn(&Card<'db>) -> bool {
+        let removed : Vec<u32> = self.game.battlefield.cards.iter().filter(|card| selector(card)).map(|card| card.id).collect();
+        for id in removed {
+            let card = self.game.battlefield.take(id).unwrap();
+            if self.game.verbose {
+                println!(" - {}: {} -> graveyard!", reason, card);
+            }
+            self.record_event(format!("{} took {}", reason, card.data.name));
+            self.turn_stats.permanents_removed += 1;
+            self.move_card(card, ZoneType::Graveyard);
+        }
+    }
+
+    // The counterspell the opponents held up this turn, if any, goes to the
+    // first spell worth it.
+    fn is_countered(&mut self, spell: &Card<'db>) -> bool {
+        if !self.counterspell_held || spell.data.cmc < self.game.interaction.counterspell_min_cmc {
+            return false;
+        }
+        self.counterspell_held = false;
+        if self.game.verbose {
+            println!(" - countered: {}", spell);
+        }
+        self.record_event(format!("{} was countered", spell.data.name));
+        self.turn_stats.spells_countered += 1;
+        return true;
+    }
+
+    // Cycles away cards we won't be able to make use of any time soon, that
+    // is spells we can't cast next turn either and lands we don't need.
+    fn try_to_cycle(&mut self) -> bool {
+        let mana_next_turn = self.mana_pool.cmc() + 1;
+        let lands_in_hand = self.game.hand.query(Types::Land).len();
+        let mut abilities = self.find_abilities_in_hand(|card, ability| {
+            ability.effect.is_draw()
+            && match card.is_type(Types::Land) {
+                true => self.lands_played >= self.land_limit() && lands_in_hand > 1,
+                false => self.cost_cmc(card) > mana_next_turn
+            }
+        });
+        if abilities.is_empty() {
+            return false;
+        }
+        abilities.sort_by_key(|(_, ability)| ability.cost.is_mana().map_or(0, |cost| cost.cmc()));
+        let (card, ability) = abilities.remove(0);
+        self.activate_from_hand(card, ability);
+        return true;
+    }
+
+    // Activates an ability of a card in hand. The card is discarded as part
+    // of the cost before the effect resolves.
+    fn activate_from_hand(&mut self, card: Card<'db>, ability: &'db Ability) {
+        if self.game.verbose {
+            println!(" - activating from hand {} :: {}", card, ability);
+        }
+        let card = self.game.hand.take(card.id).expect("card to activate wasn't in hand!!!");
+        self.record_event(format!("{} :: {}", card.data.name, ability));
+        if let Some(mana_cost) = ability.cost.is_mana() {
+            if !self.pay_mana(mana_cost) {
+                panic!("cannot pay for {}!!!", ability);
+            }
+        }
+        if let Some(life) = ability.cost.is_life() {
+            self.pay_life(&card, life);
+        }
+        self.move_card(card.clone(), ZoneType::Graveyard);
+        self.resolve_effect(&card, &ability.effect);
+    }
+
+    fn try_to_activate_ramp_ability(&mut self) -> bool {
+        let mut abilities = self.find_abilities_on_battlefield(|ability| {
+            ability.trigger.is_activated()
+            && ability.effect.is_fetch_land()
+            && ability.availability >= rand::random::<f32>()
+        });
+        if abilities.is_empty() {
+            return false;
+        }
+        abilities.sort_by(|(_, ability_a), (_, ability_b)| {
+            let cost_a = ability_a.cost.is_mana().map_or(0, |cost| cost.cmc());
+            let cost_b = ability_b.cost.is_mana().map_or(0, |cost| cost.cmc());
+            cost_a.cmp(&cost_b)
         });
         if self.game.verbose {
             for (card, ability) in &abilities {
@@ -485,6 +1298,8 @@ impl<'db, 'game> Turn<'db, 'game> {
             match &ability.effect {
                 Effect::FetchLand{to_hand: _, to_battlefield: _} => true,
                 Effect::ProduceMana(_) => true,
+                Effect::ProduceManaPer { mana: _, amount: _ } => true,
+                Effect::ManaMultiplier { filter: _, factor: _ } => ability.trigger.is_static(),
                 Effect::LandLimit(_) => self.game.hand.cards.iter().any(|card| card.is_type(Types::Land)),
                 _ => false
             }
@@ -504,6 +1319,75 @@ impl<'db, 'game> Turn<'db, 'game> {
         return true;
     }
 
+    fn try_to_play_tutor_spell(&mut self) -> bool {
+        let mut candidates = self.find_spells_in_hand(|ability| {
+            ability.trigger.is_cast()
+            && ability.effect.is_tutor()
+            && ability.availability >= rand::random::<f32>()
+        });
+        if candidates.is_empty() {
+            return false;
+        }
+        candidates.sort_by(|a, b| a.data.cmc.cmp(&b.data.cmc));
+        if self.game.verbose {
+            for card in &candidates {
+                println!(" - tutor spell candidate: {}", card);
+            }
+        }
+        let card = self.game.hand.take(candidates[0].id).unwrap();
+        self.play_card(card);
+        return true;
+    }
+
+    fn try_to_play_recursion_spell(&mut self) -> bool {
+        let mut candidates = self.find_spells_in_hand(|ability| {
+            match &ability.effect {
+                Effect::ReturnFromGraveyard { filter, destination: _, count: _ } => {
+                    ability.trigger.is_cast()
+                    && self.game.graveyard.cards.iter().any(|card| filter.matches(card.data))
+                },
+                _ => false
+            }
+        });
+        if candidates.is_empty() {
+            return false;
+        }
+        candidates.sort_by(|a, b| a.data.cmc.cmp(&b.data.cmc));
+        if self.game.verbose {
+            for card in &candidates {
+                println!(" - recursion spell candidate: {}", card);
+            }
+        }
+        let card = self.game.hand.take(candidates[0].id).unwrap();
+        self.play_card(card);
+        return true;
+    }
+
+    // Channels lands for their graveyard recursion, like 'Takenuma, Abandoned
+    // Mire', when the land drop is made and another land is kept for later.
+    fn try_to_channel(&mut self) -> bool {
+        let lands_in_hand = self.game.hand.cards.iter().filter(|card| card.is_type(Types::Land)).count();
+        if self.lands_played < self.land_limit() || lands_in_hand < 2 {
+            return false;
+        }
+        let mut abilities = self.find_abilities_in_hand(|card, ability| {
+            match &ability.effect {
+                Effect::ReturnFromGraveyard { filter, destination: _, count: _ } => {
+                    card.is_type(Types::Land)
+                    && self.game.graveyard.cards.iter().any(|card| filter.matches(card.data))
+                },
+                _ => false
+            }
+        });
+        if abilities.is_empty() {
+            return false;
+        }
+        abilities.sort_by_key(|(_, ability)| ability.cost.is_mana().map_or(0, |cost| cost.cmc()));
+        let (card, ability) = abilities.remove(0);
+        self.activate_from_hand(card, ability);
+        return true;
+    }
+
     fn try_to_activate_draw_ability(&mut self) -> bool {
         let mut abilities = self.find_abilities_on_battlefield(|ability|
             ability.trigger.is_activated()
@@ -542,7 +1426,7 @@ impl<'db, 'game> Turn<'db, 'game> {
 
     fn try_to_play_draw_spell(&mut self) -> bool {
         let mut candidates = self.find_spells_in_hand(|ability|
-            ability.effect.is_draw()
+            (ability.effect.is_draw() || ability.effect.is_dig())
             && ability.availability >= rand::random::<f32>()
         );
         if candidates.is_empty() {
@@ -559,14 +1443,34 @@ impl<'db, 'game> Turn<'db, 'game> {
         return true;
     }
 
+    // Spells the other try_to_play_xxx() cast when they are worth it, like
+    // draw spells once our hand runs low, are held back until then.
+    fn is_held_back(&self, card: &Card<'db>) -> bool {
+        return card.data.abilities.iter().flatten().any(|ability| {
+            ability.works_from(ZoneType::Battlefield)
+            && match &ability.effect {
+                Effect::FetchLand { to_hand: _, to_battlefield: _ }
+                | Effect::ProduceMana(_)
+                | Effect::ProduceManaPer { mana: _, amount: _ }
+                | Effect::LandLimit(_) => true,
+                Effect::ManaMultiplier { filter: _, factor: _ } => ability.trigger.is_static(),
+                Effect::Tutor { filter: _, destination: _, preferences: _ }
+                | Effect::ReturnFromGraveyard { filter: _, destination: _, count: _ } => ability.trigger.is_cast(),
+                effect => effect.is_draw() || effect.is_dig()
+            }
+        });
+    }
+
     fn try_to_empty_hand(&mut self) -> bool {
+        if !self.can_cast_spell() {
+            return false;
+        }
         let mut candidates : Vec<Card> = self.game.hand.cards.iter().filter(|card| {
-            if card.is_type(Types::Land)
-                || card.data.abilities.is_some() {
+            if card.is_type(Types::Land) || self.is_held_back(card) {
                 return false;
             }
             if let Some(cost) = &card.data.mana_cost {
-                if self.mana_pool.can_also_pay_for(&self.mana_spent, &cost).is_none() {
+                if !self.can_afford(&self.cost_of(card, cost)) {
                     return false;
                 }
             }
@@ -621,26 +1525,221 @@ impl<'db, 'game> Turn<'db, 'game> {
         return true;
     }
 
-    fn play_card(&mut self, mut card: Card<'db>) {
-        if card.data.enters_tapped {
+    fn try_to_cast_from_graveyard(&mut self) -> bool {
+        if !self.can_cast_spell() {
+            return false;
+        }
+        let graveyard_size = self.game.graveyard.size();
+        let mut candidates = self.find_abilities_in(ZoneType::Graveyard.into(), |ability| ability.effect.is_cast_from_graveyard());
+        candidates.retain(|(card, ability)| match (&ability.effect, ability.cost.is_mana()) {
+            (Effect::CastFromGraveyard { exile }, Some(cost)) => graveyard_size > *exile && self.can_afford(&self.cost_of(card, cost)),
+            _ => false
+        });
+        if candidates.is_empty() {
+            return false;
+        }
+        candidates.sort_by(|(a, _), (b, _)| b.data.cmc.cmp(&a.data.cmc));
+        if self.game.verbose {
+            for (card, ability) in &candidates {
+                println!(" - graveyard cast candidate: {} :: {}", card, ability);
+            }
+        }
+
+        let (card, ability) = candidates.remove(0);
+        let card = self.game.graveyard.take(card.id).unwrap();
+        if let Effect::CastFromGraveyard { exile } = ability.effect {
+            self.exile_from_graveyard(exile);
+        }
+        self.record_event(format!("cast {} from graveyard", card.data.name));
+        let cost = self.cost_of(&card, ability.cost.is_mana().unwrap());
+        self.cast_card(card, Some(cost), true);
+        return true;
+    }
+
+    // Exiles the cards least likely to be useful later, those we can't cast
+    // from the graveyard and with the lowest mana value.
+    fn exile_from_graveyard(&mut self, count: u32) {
+        let mut cards = self.game.graveyard.cards.clone();
+        cards.sort_by_key(|card| (card.data.abilities.iter().flatten().any(|ability| ability.effect.is_cast_from_graveyard()), card.data.cmc));
+        for card in cards.iter().take(count as usize) {
+            let card = self.game.graveyard.take(card.id).unwrap();
+            if self.game.verbose {
+                println!(" - {} -> exile!", card);
+            }
+            self.move_card(card, ZoneType::Exile);
+        }
+    }
+
+    fn play_card(&mut self, card: Card<'db>) {
+        let mana_cost = card.data.mana_cost.as_ref().map(|cost| self.cost_of(&card, cost));
+        self.cast_card(card, mana_cost, false);
+    }
+
+    fn is_hate_piece_in_play(&self, piece: HatePiece) -> bool {
+        return self.game.hate.contains(&piece);
+    }
+
+    // The cost of casting 'card' for 'cost', with the reductions and
+    // increases of our static abilities, and taxes like 'Thalia, Guardian of
+    // Thraben'. Reductions only take off generic mana.
+    fn cost_of(&self, card: &Card<'db>, cost: &ManaPool) -> ManaPool {
+        let mut cost = cost.clone();
+        let mut increase = 0;
+        let mut reduction = 0;
+        let mut floor = 0;
+        if self.is_hate_piece_in_play(HatePiece::Thalia) && !card.is_type(Types::Creature) && !card.is_type(Types::Land) {
+            increase += 1;
+        }
+        for ability in self.find_cost_modifiers(card) {
+            match &ability.effect {
+                Effect::CostReduction { filter, amount, floor: min } if filter.matches(card.data) => {
+                    reduction += self.count(amount);
+                    floor = floor.max(*min);
+                },
+                Effect::CostIncrease { filter, count } if filter.matches(card.data) => increase += count,
+                _ => ()
+            }
+        }
+        for _ in 0..increase {
+            cost.add_mana(&COLORLESS);
+        }
+        let reducible = cost.colorless.min(cost.cmc().saturating_sub(floor));
+        cost.colorless -= reduction.min(reducible);
+        return cost;
+    }
+
+    // The mana value we'll actually have to pay for 'card'.
+    fn cost_cmc(&self, card: &Card<'db>) -> u32 {
+        return card.data.mana_cost.as_ref().map_or(card.data.cmc, |cost| self.cost_of(card, cost).cmc());
+    }
+
+    // Static abilities changing what 'card' costs, from our permanents, from
+    // the command zone like eminence, and from the card itself like affinity.
+    fn find_cost_modifiers(&self, card: &Card<'db>) -> Vec<&'db Ability> {
+        let mut result : Vec<&Ability> = self.find_active_abilities(|ability| ability.trigger.is_static() && ability.effect.is_cost_modifier())
+            .into_iter()
+            .filter(|(source, _)| source.id != card.id)
+            .map(|(_, ability)| ability)
+            .collect();
+        result.extend(card.data.abilities.iter().flatten()
+            .filter(|ability| ability.works_from(ZoneType::Hand) && ability.trigger.is_static() && ability.effect.is_cost_modifier()));
+        return result;
+    }
+
+    fn can_cast_spell(&self) -> bool {
+        let one_spell_per_turn = self.is_hate_piece_in_play(HatePiece::RuleOfLaw) || self.is_hate_piece_in_play(HatePiece::ArchonOfEmeria);
+        return !one_spell_per_turn || self.spells_cast == 0;
+    }
+
+    // Nonbasic lands lose their abilities and tap for red under 'Blood Moon'.
+    fn is_mountain(&self, card: &Card<'db>) -> bool {
+        return self.is_hate_piece_in_play(HatePiece::BloodMoon)
+            && card.is_type(Types::Land)
+            && !card.data.is_basic();
+    }
+
+    fn is_ouphed(&self, card: &Card<'db>) -> bool {
+        return self.is_hate_piece_in_play(HatePiece::CollectorOuphe) && card.is_type(Types::Artifact);
+    }
+
+    // Whether the ability's condition, like "hand_size <= 3", holds right
+    // now. Abilities without a condition always do.
+    fn condition_holds(&self, ability: &Ability) -> bool {
+        return ability.condition.as_ref().map_or(true, |condition| condition.is_true(self));
+    }
+
+    fn can_pay_life(&self, life: u32) -> bool {
+        return self.game.life - life as i32 >= self.game.min_life;
+    }
+
+    fn pay_life(&mut self, card: &Card<'db>, life: u32) {
+        assert!(self.can_pay_life(life));
+        self.game.life -= life as i32;
+        self.turn_stats.life_paid += life;
+        if self.game.verbose {
+            println!(" - paying {} life for {}, life is now {}", life, card.data.name, self.game.life);
+        }
+    }
+
+    // Evaluates the card's enters-tapped condition against the permanents
+    // already on the battlefield. Pays the life when the card lets us, like
+    // shock lands, so only call this when the card actually enters.
+    fn enters_tapped(&mut self, card: &Card<'db>) -> bool {
+        let nonbasic_land = card.is_type(Types::Land) && !card.data.is_basic();
+        if nonbasic_land && self.is_hate_piece_in_play(HatePiece::ArchonOfEmeria) {
+            return true;
+        }
+        if self.is_mountain(card) {
+            return false;
+        }
+        let lands = || self.game.battlefield.cards.iter().filter(|c| c.is_type(Types::Land) && c.id != card.id);
+        match &card.data.enters_tapped {
+            EntersTapped::Never => false,
+            EntersTapped::Always => true,
+            EntersTapped::UnlessControl(land_types) => !lands().any(|land| {
+                land_types.iter().any(|t| land.data.matches_type_query(t))
+            }),
+            EntersTapped::UnlessAtMostOtherLands(count) => lands().count() as u32 > *count,
+            EntersTapped::UnlessAtLeastOtherLands(count) => (lands().count() as u32) < *count,
+            EntersTapped::UnlessAtLeastBasicLands(count) => (lands()
+                .filter(|land| land.data.is_basic())
+                .count() as u32) < *count,
+            // Pay up when we can afford to, like with shock lands.
+            EntersTapped::UnlessPayLife(life) => if self.can_pay_life(*life) {
+                self.pay_life(card, *life);
+                false
+            } else {
+                true
+            },
+            EntersTapped::UnlessOpponents(count) => self.game.table.opponents < *count,
+        }
+    }
+
+    // Plays the card, paying 'mana_cost' rather than the printed cost. Spells
+    // go to exile rather than the graveyard when 'exile_afterwards' is set.
+    fn cast_card(&mut self, mut card: Card<'db>, mana_cost: Option<ManaPool>, exile_afterwards: bool) {
+        if !card.is_type(Types::Land) {
+            self.spells_cast += 1;
+        }
+        let permanent = !(card.is_type(Types::Instant) || card.is_type(Types::Sorcery));
+        if !card.is_type(Types::Land) && self.is_countered(&card) {
+            if let Some(mana_cost) = &mana_cost {
+                if !self.pay_mana(mana_cost) {
+                    panic!("cannot pay for countered spell {}!!!", card);
+                }
+            }
+            // Countered permanents go to the graveyard, even when escaped.
+            self.put_spell_away(card, exile_afterwards && !permanent);
+            return;
+        }
+        if self.enters_tapped(&card) {
             card.tapped = true;
         }
+        card.entered_battlefield = self.turn_number;
         if self.game.verbose {
             println!(" - playing: {}", card);
         }
+        self.record_event(format!("played {}", card.data.name));
 
         if card.is_type(Types::Land) {
-            assert!(self.lands_played < self.land_limit);
+            assert!(self.lands_played < self.land_limit());
             self.lands_played += 1;
             self.turn_stats.lands_played += 1;
+        } else if mana_cost.is_none() && card.data.mana_cost.is_some() {
+            self.turn_stats.cards_cast_free += 1;
         } else {
             self.turn_stats.cards_played += 1;
         }
 
-        let permanent = !(card.is_type(Types::Instant) || card.is_type(Types::Sorcery));
+        if self.is_mountain(&card) && !card.tapped {
+            self.add_to_mana_pool(&card, &ManaPool::new_from_single(&RED));
+        }
 
         // Resolving card ability...
         for ability in card.data.abilities.iter().flatten() {
+            if !ability.works_from(ZoneType::Battlefield) || !self.condition_holds(ability) || self.is_mountain(&card) {
+                continue;
+            }
             match &ability.effect {
                 Effect::ProduceMana(pool) => {
                     if self.cards_in_mana_pool.contains(&card.id) {
@@ -649,32 +1748,107 @@ impl<'db, 'game> Turn<'db, 'game> {
                     // Lands, mana rocks, mana dorks, etc..
                     if permanent
                         && ability.trigger.is_activated()
-                        && (!ability.cost.is_tap() || !card.tapped)
-                        && !ability.cost.is_mana().is_some() {
+                        && !self.is_ouphed(&card)
+                        && (!ability.cost.is_tap() || !(card.tapped || card.is_summoning_sick(self.turn_number)))
+                        && ability.cost.is_mana().is_none()
+                        && ability.cost.is_life().is_none()
+                        && ability.cost.is_loyalty().is_none() {
                         self.add_to_mana_pool(&card, pool);
                     } else if ability.trigger.is_cast() {
-                        self.add_to_mana_pool(&card, pool);
+                        self.resolve_effect(&card, &ability.effect);
+                    }
+                },
+                Effect::ProduceManaPer { mana, amount } => {
+                    if self.cards_in_mana_pool.contains(&card.id) {
+                        continue;
+                    }
+                    if permanent
+                        && ability.trigger.is_activated()
+                        && ability.cost.is_tap()
+                        && !self.is_ouphed(&card)
+                        && !(card.tapped || card.is_summoning_sick(self.turn_number)) {
+                        self.add_scaled_to_mana_pool(&card, mana, amount, &ability.cost);
+                    } else if ability.trigger.is_cast() {
+                        self.resolve_effect(&card, &ability.effect);
                     }
                 },
+                Effect::ManaMultiplier { filter: _, factor: _ } => (),
                 Effect::FetchLand{to_hand: types_to_hand, to_battlefield: types_to_battlefield} => {
                     if ability.trigger.is_cast() && ability.cost.is_none() {
                         self.fetch_lands(types_to_hand, types_to_battlefield);
                     }
                 },
-                Effect::LandLimit(increase) => self.land_limit += increase,
+                // Static ones are picked up by update_static_effects().
+                Effect::LandLimit(increase) => if ability.trigger.is_cast() {
+                    self.land_limit += increase;
+                },
                 Effect::Draw(ratios) => {
                     if ability.trigger.is_cast() && ability.cost.is_none() {
                         self.draw_cards(&card, ratios);
                     }
-                }
+                },
+                Effect::Tutor { filter, destination, preferences } => {
+                    if ability.trigger.is_cast() && ability.cost.is_none() {
+                        self.tutor(filter, destination, preferences);
+                    }
+                },
+                Effect::Scry(count) => {
+                    if ability.trigger.is_cast() && ability.cost.is_none() {
+                        self.scry(*count, false);
+                    }
+                },
+                Effect::Surveil(count) => {
+                    if ability.trigger.is_cast() && ability.cost.is_none() {
+                        self.scry(*count, true);
+                    }
+                },
+                Effect::Dig { count, filter } => {
+                    if ability.trigger.is_cast() && ability.cost.is_none() {
+                        self.dig(*count, filter);
+                    }
+                },
+                Effect::ReturnFromGraveyard { filter, destination, count } => {
+                    if ability.trigger.is_cast() && ability.cost.is_none() {
+                        self.return_from_graveyard(filter, destination, *count);
+                    }
+                },
+                Effect::Cascade => {
+                    if ability.trigger.is_cast() && card.data.cmc > 0 {
+                        self.cascade(card.data.cmc - 1);
+                    }
+                },
+                Effect::Discover(count) => {
+                    if ability.trigger.is_cast() {
+                        self.cascade(*count);
+                    }
+                },
+                // The counters a permanent enters with go on once it's
+                // there, see below.
+                Effect::AddCounters { counter: _, count: _, filter: None } if permanent => (),
+                Effect::UntapLands(_)
+                | Effect::CreateToken { token: _, count: _ }
+                | Effect::DrawPer(_)
+                | Effect::AddCounters { counter: _, count: _, filter: _ }
+                | Effect::MoveCounters { counter: _, count: _, filter: _ }
+                | Effect::Proliferate
+                | Effect::PerOpponent { effect: _, chance: _ } => {
+                    if ability.trigger.is_cast() && ability.cost.is_none() {
+                        self.resolve_effect(&card, &ability.effect);
+                    }
+                },
+                Effect::NoMaximumHandSize
+                | Effect::PlayLandsFromGraveyard
+                | Effect::CastFromGraveyard { exile: _ }
+                | Effect::PlayLandsFromLibraryTop
+                | Effect::CostReduction { filter: _, amount: _, floor: _ }
+                | Effect::CostIncrease { filter: _, count: _ } => ()
             }
         }
 
         // pay mana cost
-        if let Some(mana_cost) = &card.data.mana_cost {
-            match self.mana_pool.can_also_pay_for(&self.mana_spent, &mana_cost) {
-                Some(new_mana_spent) => self.mana_spent = new_mana_spent,
-                None => panic!("cannot pay for {}!!!", card)
+        if let Some(mana_cost) = &mana_cost {
+            if !self.pay_mana(mana_cost) {
+                panic!("cannot pay for {}!!!", card);
             }
         }
 
@@ -684,16 +1858,40 @@ impl<'db, 'game> Turn<'db, 'game> {
             }
         }
 
+        if !card.is_type(Types::Land) {
+            self.trigger_spell_cast(&card);
+        }
+
         if permanent {
             if self.game.verbose {
                 println!(" - {} -> battlefield!", card);
             }
-            self.game.battlefield.add(card);
+            self.move_card(card.clone(), ZoneType::Battlefield);
+            for ability in card.data.abilities.iter().flatten() {
+                if let Effect::AddCounters { counter: _, count: _, filter: None } = ability.effect {
+                    if ability.trigger.is_cast() && ability.cost.is_none() && ability.works_from(ZoneType::Battlefield) {
+                        self.resolve_effect(&card, &ability.effect);
+                    }
+                }
+            }
+        } else {
+            self.put_spell_away(card, exile_afterwards);
+        }
+    }
+
+    // Puts a spell leaving the stack into exile when 'exile_afterwards' is
+    // set, like with flashback, and into the graveyard otherwise.
+    fn put_spell_away(&mut self, card: Card<'db>, exile_afterwards: bool) {
+        if exile_afterwards {
+            if self.game.verbose {
+                println!(" - {} -> exile!", card);
+            }
+            self.move_card(card, ZoneType::Exile);
         } else {
             if self.game.verbose {
                 println!(" - {} -> graveyard!", card);
             }
-            self.game.graveyard.add(card);
+            self.move_card(card, ZoneType::Graveyard);
         }
     }
 
@@ -701,6 +1899,9 @@ impl<'db, 'game> Turn<'db, 'game> {
     // removed from the battlefield here and needs to be put back unless it
     // is sacrificed, in which case it goes to graveyard.
     fn pay_activation_cost(&mut self, mut card: Card<'db>, cost: &Cost) {
+        if let Some(life) = cost.is_life() {
+            self.pay_life(&card, life);
+        }
         if cost.is_tap() {
             assert!(!card.tapped);
             card.tapped = true;
@@ -712,23 +1913,341 @@ impl<'db, 'game> Turn<'db, 'game> {
             if self.game.verbose {
                 println!(" - {} -> graveyard!", card);
             }
-            self.game.graveyard.add(card);
+            self.move_card(card, ZoneType::Graveyard);
 
         } else {
             // put the card back now we've modified it..
             self.game.battlefield.add(card);
         }
         if let Some(mana_cost) = cost.is_mana() {
-            self.mana_spent.add_pool(&mana_cost);
-            assert!(self.mana_pool.can_pay_for(&self.mana_spent));
-        }
-    }
-
-    fn add_to_mana_pool(&mut self, card: &Card<'db>, mana_produced: &ManaPool) {
-        self.mana_pool.add_pool(mana_produced);
-        self.cards_in_mana_pool.insert(card.id);
-        if self.game.verbose {
-            println!(" - add to mana pool: {}, {}", mana_produced, card);
+            if !self.pay_mana(mana_cost) {
+                panic!("cannot pay for {}!!!", cost);
+            }
+        }
+    }
+
+    // Resolves "whenever you cast a spell" abilities of permanents on the
+    // battlefield. The spell itself is not on the battlefield yet, so it
+    // won't trigger on its own casting.
+    fn trigger_spell_cast(&mut self, spell: &Card<'db>) {
+        for (card, ability) in self.find_active_abilities(|ability| {
+            ability.trigger.is_spell_cast(spell.data)
+            && (ability.availability == 1.0 || rand::random::<f32>() < ability.availability)
+        }) {
+            if self.game.verbose {
+                println!(" - {} triggers on casting {}", card, spell.data.name);
+            }
+            self.record_event(format!("{} :: {}", card.data.name, ability));
+            self.resolve_effect(&card, &ability.effect);
+        }
+    }
+
+    // Resolves the effect of a triggered ability, or an ability activated
+    // from hand, of 'card'.
+    fn resolve_effect(&mut self, card: &Card<'db>, effect: &'db Effect) {
+        match effect {
+            // Not using add_to_mana_pool() as that would mark the card's
+            // own mana ability as already added to the pool.
+            Effect::ProduceMana(mana) => {
+                self.mana_pool.add_pool(mana);
+                if self.game.verbose {
+                    println!(" - add to mana pool: {}, {}", mana, card);
+                }
+            },
+            Effect::FetchLand{ to_hand: hand, to_battlefield: bf } => self.fetch_lands(hand, bf),
+            Effect::LandLimit(increase) => self.land_limit += increase,
+            Effect::Draw(ratios) => self.draw_cards(card, ratios),
+            Effect::Tutor { filter, destination, preferences } => self.tutor(filter, destination, preferences),
+            Effect::Scry(count) => self.scry(*count, false),
+            Effect::Surveil(count) => self.scry(*count, true),
+            Effect::Dig { count, filter } => self.dig(*count, filter),
+            Effect::ReturnFromGraveyard { filter, destination, count } => self.return_from_graveyard(filter, destination, *count),
+            Effect::NoMaximumHandSize => (),
+            Effect::PlayLandsFromGraveyard => (),
+            Effect::CastFromGraveyard { exile: _ } => (),
+            Effect::PlayLandsFromLibraryTop => (),
+            Effect::CostReduction { filter: _, amount: _, floor: _ } => (),
+            Effect::CostIncrease { filter: _, count: _ } => (),
+            Effect::Cascade => if card.data.cmc > 0 {
+                self.cascade(card.data.cmc - 1);
+            },
+            Effect::Discover(count) => self.cascade(*count),
+            Effect::ProduceManaPer { mana, amount } => {
+                let produced = mana.multiplied(self.count(amount));
+                self.mana_pool.add_pool(&produced);
+                if self.game.verbose {
+                    println!(" - add to mana pool: {}, {}", produced, card);
+                }
+            },
+            Effect::ManaMultiplier { filter: _, factor: _ } => (),
+            Effect::UntapLands(count) => self.untap_lands(*count),
+            Effect::CreateToken { token, count } => self.create_tokens(token, *count),
+            Effect::DrawPer(amount) => {
+                let count = self.count(amount);
+                self.draw_cards(card, &vec![count]);
+            },
+            Effect::AddCounters { counter, count, filter } => self.add_counters(card, *counter, *count, filter),
+            Effect::MoveCounters { counter, count, filter } => self.move_counters(*counter, *count, filter),
+            Effect::Proliferate => self.proliferate(),
+            Effect::PerOpponent { effect, chance } => {
+                for _ in 0..self.game.table.opponents {
+                    if rand::random::<f32>() < *chance {
+                        self.resolve_effect(card, effect);
+                    }
+                }
+            },
+        }
+    }
+
+    // Without a filter the counters go on the card itself, otherwise on the
+    // matching permanent that already has the most of them.
+    fn add_counters(&mut self, card: &Card<'db>, counter: CounterType, count: u32, filter: &Option<CardFilter>) {
+        let target = match filter {
+            None => self.game.battlefield.cards.iter_mut().find(|c| c.id == card.id),
+            Some(filter) => self.game.battlefield.cards.iter_mut()
+                .filter(|c| filter.matches(c.data))
+                .rev()
+                .max_by_key(|c| c.counters(counter))
+        };
+        if let Some(target) = target {
+            target.add_counters(counter, count);
+            if self.game.verbose {
+                println!(" - adding {}x {} counters: {}", count, counter, target);
+            }
+        }
+    }
+
+    // Moves counters from the matching permanent with the fewest of them to
+    // the one with the most, growing our biggest threat.
+    fn move_counters(&mut self, counter: CounterType, count: u32, filter: &CardFilter) {
+        let candidates : Vec<(u32, u32)> = self.game.battlefield.cards.iter()
+            .filter(|c| filter.matches(c.data))
+            .map(|c| (c.id, c.counters(counter)))
+            .collect();
+        let from = candidates.iter().filter(|(_, n)| *n > 0).min_by_key(|(_, n)| *n);
+        let to = candidates.iter().max_by_key(|(_, n)| *n);
+        if let (Some((from_id, _)), Some((to_id, _))) = (from, to) {
+            if from_id == to_id {
+                return;
+            }
+            let moved = self.game.battlefield.cards.iter_mut().find(|c| c.id == *from_id).unwrap().remove_counters(counter, count);
+            let target = self.game.battlefield.cards.iter_mut().find(|c| c.id == *to_id).unwrap();
+            target.add_counters(counter, moved);
+            if self.game.verbose {
+                println!(" - moving {}x {} counters: {}", moved, counter, target);
+            }
+        }
+    }
+
+    // Every permanent is ours, so we proliferate all of them, except for the
+    // -1/-1 counters.
+    fn proliferate(&mut self) {
+        for card in self.game.battlefield.cards.iter_mut() {
+            let counters : Vec<CounterType> = card.counters.keys()
+                .filter(|counter| **counter != CounterType::MinusOne)
+                .cloned()
+                .collect();
+            for counter in counters {
+                card.add_counters(counter, 1);
+            }
+            if self.game.verbose && !card.counters.is_empty() {
+                println!(" - proliferating: {}", card);
+            }
+        }
+    }
+
+    // Untapping a land we tapped for mana is as good as adding its mana
+    // again, so we take the lands producing the most colors.
+    fn untap_lands(&mut self, count: u32) {
+        let mut lands : Vec<Card<'db>> = self.game.battlefield.query(Types::Land)
+            .into_iter()
+            .filter(|land| self.cards_in_mana_pool.contains(&land.id) && land.produced_mana().is_some())
+            .collect();
+        sort_cards_on_colors_produced(&mut lands);
+        for land in lands.iter().take(count as usize) {
+            let mana = land.produced_mana().unwrap().multiplied(self.mana_multiplier(land));
+            self.mana_pool.add_pool(&mana);
+            if self.game.verbose {
+                println!(" - untapping: {}, add to mana pool: {}", land, mana);
+            }
+        }
+    }
+
+    // Tokens enter the battlefield like any other permanent, triggering
+    // abilities on creatures entering.
+    fn create_tokens(&mut self, token: &'db CardData, count: u32) {
+        for _ in 0..count {
+            let mut card = Card::new(token);
+            card.id = self.game.next_id;
+            card.entered_battlefield = self.turn_number;
+            self.game.next_id += 1;
+            if self.game.verbose {
+                println!(" - creating token: {}", card);
+            }
+            self.move_card(card, ZoneType::Battlefield);
+        }
+    }
+
+    // Adds the mana from tapping 'card' to the pool, multiplied by static
+    // effects like 'Mana Reflection'.
+    fn add_to_mana_pool(&mut self, card: &Card<'db>, mana_produced: &ManaPool) {
+        let mana = mana_produced.multiplied(self.mana_multiplier(card));
+        self.mana_pool.add_pool(&mana);
+        self.cards_in_mana_pool.insert(card.id);
+        if self.game.verbose {
+            println!(" - add to mana pool: {}, {}", mana, card);
+        }
+    }
+
+    // Taps 'card' for mana depending on the board, paying the mana part of
+    // the cost, like on 'Cabal Coffers'. Skipped unless it's a net gain.
+    fn add_scaled_to_mana_pool(&mut self, card: &Card<'db>, mana: &ManaPool, amount: &Amount, cost: &Cost) {
+        let produced = mana.multiplied(self.count(amount));
+        if let Some(mana_cost) = cost.is_mana() {
+            if produced.cmc() <= mana_cost.cmc() || !self.pay_mana(mana_cost) {
+                return;
+            }
+        } else if produced.cmc() == 0 {
+            return;
+        }
+        self.add_to_mana_pool(card, &produced);
+    }
+
+    // Whether we can pay 'cost' on top of the mana already spent, converting
+    // mana if needed.
+    fn can_afford(&self, cost: &ManaPool) -> bool {
+        return self.can_afford_from(&self.mana_pool, cost);
+    }
+
+    // Like can_afford(), but out of 'pool' rather than the whole mana pool.
+    fn can_afford_from(&self, pool: &ManaPool, cost: &ManaPool) -> bool {
+        return self.find_mana_conversions(pool, cost).is_some();
+    }
+
+    // Pays 'cost' on top of the mana already spent, converting mana first if
+    // needed. Nothing is paid when we can't afford it.
+    fn pay_mana(&mut self, cost: &ManaPool) -> bool {
+        self.convert_mana_for(cost);
+        match self.mana_pool.can_also_pay_for(&self.mana_spent, cost) {
+            Some(spent) => self.mana_spent = spent,
+            None => return false
+        }
+        return true;
+    }
+
+    // The mana converters to use for paying 'cost' out of 'pool', none when
+    // the pool pays for it as is. Converters are only used when the pool
+    // can't, like when a color is missing, the mana spent on them is
+    // otherwise wasted.
+    fn find_mana_conversions(&self, pool: &ManaPool, cost: &ManaPool) -> Option<Vec<(Card<'db>, &'db Ability)>> {
+        let mut conversions : Vec<(Card, &Ability)> = Vec::new();
+        if pool.can_also_pay_for(&self.mana_spent, cost).is_some() {
+            return Some(conversions);
+        }
+        let mut pool = pool.clone();
+        let mut spent = self.mana_spent.clone();
+        let mut life = 0;
+        // Paying life is the last resort.
+        let mut converters = self.find_abilities_on_battlefield(|ability| ability.is_mana_converter());
+        converters.sort_by_key(|(_, ability)| ability.cost.is_life().is_some());
+        let mut converters = converters.into_iter();
+        while pool.can_also_pay_for(&spent, cost).is_none() {
+            let (card, ability) = converters.next()?;
+            if conversions.iter().any(|(converter, _)| converter.id == card.id) {
+                continue;
+            }
+            let life_cost = ability.cost.is_life().unwrap_or(0);
+            if !self.can_pay_life(life + life_cost) {
+                continue;
+            }
+            let (converted_pool, converted_spent) = self.converted(&pool, &spent, &card, ability);
+            if converted_pool.can_pay_for(&converted_spent) {
+                pool = converted_pool;
+                spent = converted_spent;
+                life += life_cost;
+                conversions.push((card, ability));
+            }
+        }
+        return Some(conversions);
+    }
+
+    // The mana pool and mana spent after using 'ability' of 'card' to convert
+    // mana. A land already tapped for mana gives that mana back.
+    fn converted(&self, pool: &ManaPool, spent: &ManaPool, card: &Card<'db>, ability: &Ability) -> (ManaPool, ManaPool) {
+        let mut pool = pool.clone();
+        let mut spent = spent.clone();
+        let multiplier = self.mana_multiplier(card);
+        if self.cards_in_mana_pool.contains(&card.id) {
+            if let Some(produced) = card.produced_mana() {
+                pool.remove_exact_pool(&produced.multiplied(multiplier));
+            }
+        }
+        if let Effect::ProduceMana(produced) = &ability.effect {
+            if let Some(mana_cost) = ability.cost.is_mana() {
+                spent.add_pool(mana_cost);
+            }
+            pool.add_pool(&produced.multiplied(multiplier));
+        }
+        return (pool, spent);
+    }
+
+    // Uses mana converters, like signets, when we need them to pay 'cost'.
+    fn convert_mana_for(&mut self, cost: &ManaPool) {
+        for (card, ability) in self.find_mana_conversions(&self.mana_pool, cost).into_iter().flatten() {
+            let (pool, spent) = self.converted(&self.mana_pool, &self.mana_spent, &card, ability);
+            if self.game.verbose {
+                println!(" - converting mana: {} :: {}", card, ability);
+            }
+            if let Some(life) = ability.cost.is_life() {
+                self.pay_life(&card, life);
+            }
+            self.mana_pool = pool;
+            self.mana_spent = spent;
+            self.cards_in_mana_pool.insert(card.id);
+            if let Some(converter) = self.game.battlefield.cards.iter_mut().find(|c| c.id == card.id) {
+                converter.tapped = true;
+            }
+            self.record_event(format!("{} :: {}", card.data.name, ability));
+        }
+    }
+
+    fn mana_multiplier(&self, card: &Card<'db>) -> u32 {
+        return self.find_active_abilities(|ability| ability.trigger.is_static())
+            .iter()
+            .filter_map(|(_, ability)| match &ability.effect {
+                Effect::ManaMultiplier { filter, factor } if filter.matches(card.data) => Some(*factor),
+                _ => None
+            })
+            .product();
+    }
+
+    fn count(&self, amount: &Amount) -> u32 {
+        let permanents = &self.game.battlefield.cards;
+        match amount {
+            Amount::Count(filter) => permanents.iter().filter(|card| filter.matches(card.data)).count() as u32,
+            Amount::Devotion(color) => {
+                let devotion = |color: Color| permanents
+                    .iter()
+                    .filter_map(|card| card.data.mana_cost.as_ref())
+                    .map(|cost| cost.devotion(color))
+                    .sum::<u32>();
+                match color {
+                    Some(color) => devotion(*color),
+                    None => [Color::Black, Color::Blue, Color::Green, Color::Red, Color::White]
+                        .into_iter()
+                        .map(devotion)
+                        .max()
+                        .unwrap()
+                }
+            },
+            Amount::Counters(counter, filter) => permanents.iter()
+                .filter(|card| filter.matches(card.data))
+                .map(|card| card.counters(*counter))
+                .sum(),
+            Amount::WithCounters(counter, filter) => permanents.iter()
+                .filter(|card| filter.matches(card.data) && card.counters(*counter) > 0)
+                .count() as u32,
+            Amount::Expr(expr) => expr.evaluate(self).max(0) as u32,
         }
     }
 
@@ -739,6 +2258,8 @@ impl<'db, 'game> Turn<'db, 'game> {
         let index = rand::random::<usize>() % ratios.len();
         self.game.draw_cards(ratios[index]);
         self.turn_stats.cards_drawn += ratios[index];
+        *self.turn_stats.cards_drawn_by.entry(card.data.name.clone()).or_insert(0) += ratios[index];
+        self.handle_zone_changes();
     }
 
     fn fetch_lands(&mut self, types_to_hand: &Vec<String>, types_to_battlefield: &Vec<String>) {
@@ -753,7 +2274,7 @@ impl<'db, 'game> Turn<'db, 'game> {
                 if self.game.verbose {
                     println!(" - fetch to hand: {}, type={}, preference={:?}", card, type_to_hand, preference);
                 }
-                self.game.hand.add(card);
+                self.move_card(card, ZoneType::Hand);
             } else if self.game.verbose {
                 println!(" - no cards of type='{}' in library, fetch to hand failed...", type_to_hand);
             }
@@ -763,11 +2284,11 @@ impl<'db, 'game> Turn<'db, 'game> {
             let maybe_card = self.game.library.take_land(type_to_battlefield, &preference);
             if let Some(mut card) = maybe_card {
                 card.tapped = true;
-                self.turn_stats.lands_cheated += 1;
+                card.entered_battlefield = self.turn_number;
                 if self.game.verbose {
                     println!(" - fetch to battlefield {}, type={}, preference={:?}", card, type_to_battlefield, preference);
                 }
-                self.game.battlefield.add(card);
+                self.move_card(card, ZoneType::Battlefield);
             } else if self.game.verbose {
                 println!(" - no cards of type='{}' in library, fetch to battlefield failed...", type_to_battlefield);
             }
@@ -775,6 +2296,213 @@ impl<'db, 'game> Turn<'db, 'game> {
         self.game.library.shuffle();
     }
 
+    fn tutor(&mut self, filter: &CardFilter, destination: &Destination, preferences: &Vec<TutorPreference>) {
+        let candidates : Vec<Card<'db>> = self.game.library.cards
+            .iter()
+            .filter(|card| filter.matches(card.data))
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            if self.game.verbose {
+                println!(" - no cards matching '{}' in library, tutor failed...", filter);
+            }
+            self.game.library.shuffle();
+            return;
+        }
+
+        let id = self.choose_tutor_target(&candidates, preferences);
+        let card = self.game.library.take(id).unwrap();
+        self.game.library.shuffle();
+        if self.game.verbose {
+            println!(" - tutor {:?}: {}", destination, card);
+        }
+        self.record_event(format!("tutored {} to {:?}", card.data.name, destination));
+
+        self.put_into(card, destination);
+    }
+
+    fn put_into(&mut self, mut card: Card<'db>, destination: &Destination) {
+        match destination {
+            Destination::Hand => self.move_card(card, ZoneType::Hand),
+            Destination::LibraryTop => self.move_card(card, ZoneType::Library),
+            Destination::Battlefield => {
+                card.tapped = self.enters_tapped(&card);
+                card.entered_battlefield = self.turn_number;
+                self.move_card(card, ZoneType::Battlefield);
+            }
+        }
+    }
+
+    fn return_from_graveyard(&mut self, filter: &CardFilter, destination: &Destination, count: u32) {
+        for _ in 0..count {
+            let candidates : Vec<Card<'db>> = self.game.graveyard.cards
+                .iter()
+                .filter(|card| filter.matches(card.data))
+                .cloned()
+                .collect();
+            if candidates.is_empty() {
+                if self.game.verbose {
+                    println!(" - no cards matching '{}' in graveyard...", filter);
+                }
+                return;
+            }
+
+            let id = self.choose_recursion_target(&candidates);
+            let card = self.game.graveyard.take(id).unwrap();
+            if self.game.verbose {
+                println!(" - return from graveyard {:?}: {}", destination, card);
+            }
+            self.record_event(format!("returned {} to {:?}", card.data.name, destination));
+            self.put_into(card, destination);
+        }
+    }
+
+    // A land if we're out of them, otherwise the most expensive spell.
+    fn choose_recursion_target(&self, candidates: &Vec<Card<'db>>) -> u32 {
+        return candidates.iter()
+            .find(|card| card.is_type(Types::Land) && self.wants_card(card, 0))
+            .or_else(|| candidates.iter().filter(|card| !card.is_type(Types::Land)).max_by_key(|card| card.data.cmc))
+            .unwrap_or(&candidates[0])
+            .id;
+    }
+
+    // Picks the first card matching the preferences, in order. Without a
+    // match, we go for the most expensive card we can cast next turn, or the
+    // cheapest one if none of them are within reach.
+    fn choose_tutor_target(&self, candidates: &Vec<Card<'db>>, preferences: &Vec<TutorPreference>) -> u32 {
+        for preference in preferences {
+            let found = match preference {
+                TutorPreference::Matching(filter) => candidates.iter().find(|card| filter.matches(card.data)),
+                TutorPreference::MissingColorSource => {
+                    let wanted = Self::evaluate_desired_mana_colors(&self.game.hand, &self.mana_pool);
+                    wanted.iter().flatten().find_map(|color| candidates.iter().find(|card| {
+                        card.data.produced_mana.as_ref().map_or(false, |mana| mana.contains(*color))
+                    }))
+                }
+            };
+            if let Some(card) = found {
+                return card.id;
+            }
+        }
+
+        let mana_next_turn = self.mana_pool.cmc() + 1;
+        let castable = candidates.iter()
+            .filter(|card| self.cost_cmc(card) <= mana_next_turn)
+            .max_by_key(|card| card.data.cmc);
+        return match castable {
+            Some(card) => card.id,
+            None => candidates.iter().min_by_key(|card| card.data.cmc).unwrap().id
+        };
+    }
+
+    // Decides if a card seen on top of the library is worth keeping there:
+    // a land when we're out of lands, or a spell we can cast next turn.
+    fn wants_card(&self, card: &Card<'db>, lands_kept: u32) -> bool {
+        if card.is_type(Types::Land) {
+            return self.game.hand.query(Types::Land).len() as u32 + lands_kept == 0;
+        }
+        return self.cost_cmc(card) <= self.mana_pool.cmc() + 1;
+    }
+
+    // Scry, or surveil when 'to_graveyard' is set. The cards we want stay on
+    // top in the order they were in, the rest goes to the bottom or the
+    // graveyard.
+    fn scry(&mut self, count: u32, to_graveyard: bool) {
+        let mut kept : Vec<Card<'db>> = Vec::new();
+        let mut lands_kept = 0;
+        for card in self.game.library.take_top(count) {
+            if self.wants_card(&card, lands_kept) {
+                if card.is_type(Types::Land) {
+                    lands_kept += 1;
+                }
+                if self.game.verbose {
+                    println!(" - scry, keeping on top: {}", card);
+                }
+                kept.push(card);
+            } else if to_graveyard {
+                if self.game.verbose {
+                    println!(" - surveil, {} -> graveyard!", card);
+                }
+                self.move_card(card, ZoneType::Graveyard);
+            } else {
+                if self.game.verbose {
+                    println!(" - scry, {} -> bottom", card);
+                }
+                self.game.library.put_on_bottom(card);
+            }
+        }
+        for card in kept.into_iter().rev() {
+            self.game.library.put_on_top(card);
+        }
+    }
+
+    // Looks at the top cards and puts one matching the filter into our hand,
+    // preferring a land we need, then the most expensive spell we can cast
+    // next turn. The rest goes to the bottom.
+    fn dig(&mut self, count: u32, filter: &CardFilter) {
+        let cards = self.game.library.take_top(count);
+        let matching = || cards.iter().filter(|card| filter.matches(card.data));
+        let choice = matching()
+            .find(|card| card.is_type(Types::Land) && self.wants_card(card, 0))
+            .or_else(|| matching()
+                .filter(|card| !card.is_type(Types::Land) && self.wants_card(card, 0))
+                .max_by_key(|card| card.data.cmc))
+            .or_else(|| matching().min_by_key(|card| card.data.cmc))
+            .map(|card| card.id);
+
+        for card in cards {
+            if Some(card.id) == choice {
+                if self.game.verbose {
+                    println!(" - dig, to hand: {}", card);
+                }
+                self.record_event(format!("dug up {}", card.data.name));
+                self.move_card(card, ZoneType::Hand);
+            } else {
+                self.game.library.put_on_bottom(card);
+            }
+        }
+    }
+
+    // Exiles cards from the top of the library until we hit a nonland card
+    // with mana value 'max_cmc' or less, which is cast without paying its
+    // mana cost. The other exiled cards go to the bottom of the library.
+    fn cascade(&mut self, max_cmc: u32) {
+        let mut exiled: Vec<u32> = Vec::new();
+        let mut hit: Option<Card<'db>> = None;
+        while let Some(card) = self.game.library.draw() {
+            if !card.is_type(Types::Land) && card.data.cmc <= max_cmc {
+                hit = Some(card);
+                break;
+            }
+            exiled.push(card.id);
+            self.move_card(card, ZoneType::Exile);
+        }
+
+        if self.game.verbose {
+            println!(" - cascade, cmc<={}, exiled {} cards, hit={}", max_cmc, exiled.len(),
+                     hit.as_ref().map_or("nothing".to_string(), |card| card.to_string()));
+        }
+        // The exiled cards go to the bottom in a random order
+        exiled.shuffle(&mut rand::thread_rng());
+        for id in exiled {
+            let card = self.game.exile.take(id).expect("cascaded card wasn't in exile!!!");
+            self.game.move_card_to_bottom(card);
+        }
+        self.handle_zone_changes();
+
+        if let Some(card) = hit {
+            if !self.can_cast_spell() {
+                if self.game.verbose {
+                    println!(" - can't cast {}, one spell each turn", card);
+                }
+                self.game.move_card_to_bottom(card);
+                return;
+            }
+            self.record_event(format!("cast {} for free", card.data.name));
+            self.cast_card(card, None, false);
+        }
+    }
+
     fn evaluate_desired_mana_colors(zone: &Zone, mana_pool: &ManaPool) -> Option<Vec<Color>> {
         let mut pips_in_zone = zone.count_pips_in_mana_costs();
         let has_pips_in_zone = pips_in_zone.normalize();
@@ -799,8 +2527,8 @@ impl<'db, 'game> Turn<'db, 'game> {
             let b_has_cost = b.data.additional_cost.is_some();
 
             if a_has_cost == b_has_cost {
-                let a_ready = !a.data.enters_tapped;
-                let b_ready = !b.data.enters_tapped;
+                let a_ready = a.data.enters_tapped != EntersTapped::Always;
+                let b_ready = b.data.enters_tapped != EntersTapped::Always;
                 if a_ready == b_ready {
                     return std::cmp::Ordering::Equal;
                 } else if a_ready {
@@ -819,10 +2547,17 @@ impl<'db, 'game> Turn<'db, 'game> {
         if self.game.verbose {
             println!(" - returning {} to hand", land);
         }
-        self.game.hand.add(land);
+        self.move_card(land, ZoneType::Hand);
     }
 }
 
+// How many times something happening 'rate' times on average happens this
+// time, like 1.5 spells a turn being either one or two spells.
+fn occurrences(rate: f32) -> u32 {
+    let whole = rate.floor();
+    return whole as u32 + if rand::random::<f32>() < rate - whole { 1 } else { 0 };
+}
+
 fn sort_cards_on_colors_produced(cards : &mut Vec<Card>) {
     cards.sort_by(|a, b| {
         let colors_in_a : u32 = match &a.data.produced_mana {
@@ -842,6 +2577,21 @@ mod tests {
 
     use super::*;
 
+    fn settings() -> Settings {
+        return Settings {
+            draw_card_on_turn_one: false,
+            turn_count: 1,
+            mulligan: MulliganType::None,
+            max_hand_size: 7,
+            format: Format::Commander,
+            min_life: 10,
+            loyalty: LoyaltyPolicy::Grow,
+            table: Table::new(3),
+            interaction: Interaction::none(),
+            hate: Vec::new(),
+        };
+    }
+
     #[test]
     fn test_game_sort_cards_on_colors_produced() {
         let swamp_data = CardData::make_swamp_data();
@@ -862,6 +2612,1063 @@ mod tests {
         assert_eq!(cards[2].id, 1); // swamp
         assert_eq!(cards[3].id, 2); // elk
     }
+
+    #[test]
+    fn test_game_choose_discards() {
+        let plains_data = CardData::make_plains_data();
+        let elk_data = CardData::make_elk_data();
+        let sol_ring_data = CardData::make_sol_ring_data();
+
+        let mut game = Game::new();
+        for id in 1..4 {
+            game.hand.add(Card::new_with_id(id, &plains_data));
+        }
+        game.hand.add(Card::new_with_id(4, &elk_data));
+        game.hand.add(Card::new_with_id(5, &sol_ring_data));
+
+        let turn = Turn::new(&mut game, 1);
+        let discards : Vec<u32> = turn.choose_discards(4).iter().map(|c| c.id).collect();
+        assert_eq!(discards.len(), 4);
+        // excess lands first, one is kept for the next land drop..
+        assert!(discards[0..2].iter().all(|id| *id <= 3));
+        // then the elk we can't cast next turn, then the sol ring
+        assert_eq!(discards[2], 4);
+        assert_eq!(discards[3], 5);
+    }
+
+    #[test]
+    fn test_game_move_card() {
+        let plains_data = CardData::make_plains_data();
+
+        let mut game = Game::new();
+        game.library.add(Card::new_with_id(1, &plains_data));
+        game.draw_cards(1);
+        assert_eq!(game.hand.cards[0].zone, ZoneType::Hand);
+        assert_eq!(game.zone_changes.len(), 1);
+        assert_eq!(game.zone_changes[0].from, ZoneType::Library);
+        assert_eq!(game.zone_changes[0].to, ZoneType::Hand);
+        game.zone_changes.clear();
+
+        let mut turn = Turn::new(&mut game, 1);
+        let card = turn.game.hand.take(1).unwrap();
+        turn.move_card(card, ZoneType::Battlefield);
+        assert!(turn.game.zone_changes.is_empty());
+        assert_eq!(turn.game.battlefield.cards[0].zone, ZoneType::Battlefield);
+        assert_eq!(turn.lands_entered, 1);
+    }
+
+    #[test]
+    fn test_game_count_amount() {
+        let plains_data = CardData::make_plains_data();
+        let elk_data = CardData::make_elk_data();
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+        game.battlefield.add(Card::new_with_id(2, &elk_data));
+        game.battlefield.add(Card::new_with_id(3, &elk_data));
+
+        let turn = Turn::new(&mut game, 1);
+        assert_eq!(turn.count(&Amount::Count(parse_card_filter("creature").unwrap())), 2);
+        let mut plains = CardFilter::any();
+        plains.subtype = Some("Plains".to_string());
+        assert_eq!(turn.count(&Amount::Count(plains)), 1);
+        assert_eq!(turn.count(&Amount::Devotion(Some(Color::Green))), 2);
+        assert_eq!(turn.count(&Amount::Devotion(Some(Color::Black))), 0);
+        assert_eq!(turn.count(&Amount::Devotion(None)), 2);
+    }
+
+    #[test]
+    fn test_game_pay_life() {
+        let plains_data = CardData::make_plains_data();
+        let mut shock_land_data = CardData::make_plains_data();
+        shock_land_data.enters_tapped = EntersTapped::UnlessPayLife(2);
+
+        let mut game = Game::new();
+        game.life = 13;
+        game.min_life = 10;
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+
+        let mut turn = Turn::new(&mut game, 1);
+        assert!(turn.can_pay_life(3));
+        assert!(!turn.can_pay_life(4));
+        assert!(!turn.enters_tapped(&Card::new_with_id(2, &shock_land_data)));
+        assert_eq!(turn.game.life, 11);
+        assert_eq!(turn.turn_stats.life_paid, 2);
+        // paying again would take us below the threshold
+        assert!(turn.enters_tapped(&Card::new_with_id(3, &shock_land_data)));
+        assert_eq!(turn.game.life, 11);
+    }
+
+    #[test]
+    fn test_game_pay_life_for_mana() {
+        let plains_data = CardData::make_plains_data();
+        let mut canopy_data = CardData::make_card_data("Horizon Canopy", "Land", "");
+        canopy_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Activated,
+            cost: Cost::TapLife(1),
+            effect: Effect::ProduceMana(ManaPool::new_from_string("{G}").unwrap()),
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }]);
+        let mut library_data = CardData::make_card_data("Sylvan Library", "Enchantment", "{1}{G}");
+        library_data.abilities = Some(vec![Ability {
+            trigger: Trigger::BeginningOf(Phase::Draw),
+            cost: Cost::Life(1),
+            effect: Effect::Draw(vec![1]),
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }]);
+
+        let mut game = Game::new();
+        game.life = 12;
+        game.min_life = 10;
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+        game.battlefield.add(Card::new_with_id(2, &canopy_data));
+        game.battlefield.add(Card::new_with_id(3, &canopy_data));
+        game.battlefield.add(Card::new_with_id(4, &library_data));
+        game.battlefield.add(Card::new_with_id(5, &library_data));
+        for id in 10..20 {
+            game.library.add(Card::new_with_id(id, &plains_data));
+        }
+
+        // life is only paid when the mana is used
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        assert_eq!(turn.mana_pool.cmc(), 1);
+        assert_eq!(turn.game.life, 12);
+        let cost = |text: &str| ManaPool::new_from_string(text).unwrap();
+        assert!(turn.can_afford(&cost("{G}{G}{W}")));
+        turn.game.life = 11;
+        assert!(turn.can_afford(&cost("{G}{W}")));
+        assert!(!turn.can_afford(&cost("{G}{G}")));
+        turn.convert_mana_for(&cost("{G}{W}"));
+        assert_eq!(turn.game.life, 10);
+        assert_eq!(turn.mana_pool.cmc(), 2);
+
+        // both triggers are found up front, only the first one can pay
+        turn.game.life = 11;
+        turn.begin_phase(Phase::Draw);
+        turn.trigger_phase_abilities();
+        assert_eq!(turn.game.life, 10);
+        assert_eq!(turn.game.hand.size(), 1);
+    }
+
+    #[test]
+    fn test_game_loyalty_abilities() {
+        let plains_data = CardData::make_plains_data();
+        let mut garruk_data = CardData::make_card_data("Garruk Wildspeaker", "Legendary Planeswalker — Garruk", "{2}{G}{G}");
+        garruk_data.loyalty = Some(3);
+        garruk_data.abilities = Some(vec![
+            Ability { trigger: Trigger::Activated, cost: Cost::Loyalty(1), effect: Effect::UntapLands(2), availability: 1.0, zones: ZoneType::Battlefield.into(), condition: None },
+            Ability { trigger: Trigger::Activated, cost: Cost::Loyalty(-1), availability: 1.0, zones: ZoneType::Battlefield.into(), condition: None,
+                effect: Effect::CreateToken { token: Box::new(CardData::new_token("Beast Token", "Token Creature — Beast")), count: 1 } },
+            Ability { trigger: Trigger::Activated, cost: Cost::Loyalty(-4), effect: Effect::Draw(vec![1]), availability: 1.0, zones: ZoneType::Battlefield.into(), condition: None },
+        ]);
+
+        let mut welcoming_data = CardData::make_sol_ring_data();
+        welcoming_data.abilities = Some(vec![Ability {
+            trigger: Trigger::ZoneChange { from: None, to: Some(ZoneType::Battlefield), filter: parse_card_filter("creature").unwrap() },
+            cost: Cost::None, effect: Effect::AddCounters { counter: CounterType::Charge, count: 1, filter: None },
+            availability: 1.0, zones: ZoneType::Battlefield.into(), condition: None
+        }]);
+
+        let mut game = Game::new();
+        game.next_id = 10;
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+        game.move_card(Card::new_with_id(2, &garruk_data), ZoneType::Battlefield);
+        game.battlefield.add(Card::new_with_id(3, &welcoming_data));
+        assert_eq!(game.battlefield.cards[1].counters(CounterType::Loyalty), 3);
+
+        {
+            // the plains adds its mana once more
+            let mut turn = Turn::new(&mut game, 1);
+            turn.untap_step();
+            assert!(turn.try_to_activate_loyalty_ability(LoyaltyPolicy::Grow));
+            assert_eq!(turn.mana_pool.cmc(), 2);
+            assert!(!turn.try_to_activate_loyalty_ability(LoyaltyPolicy::Grow));
+        }
+        assert_eq!(game.battlefield.cards.iter().find(|c| c.id == 2).unwrap().counters(CounterType::Loyalty), 4);
+
+        {
+            // -4 would kill it, so the beast it is
+            let mut turn = Turn::new(&mut game, 2);
+            assert!(turn.try_to_activate_loyalty_ability(LoyaltyPolicy::Spend));
+        }
+        assert_eq!(game.battlefield.cards.iter().find(|c| c.id == 2).unwrap().counters(CounterType::Loyalty), 3);
+        let beast = game.battlefield.cards.iter().find(|c| c.id == 10).unwrap();
+        assert!(beast.is_token() && beast.is_type(Types::Creature));
+        assert_eq!(beast.zone, ZoneType::Battlefield);
+        // entering like any other creature
+        assert_eq!(game.battlefield.cards.iter().find(|c| c.id == 3).unwrap().counters(CounterType::Charge), 1);
+
+        // tokens don't go anywhere when they leave the battlefield
+        let beast = game.battlefield.take(10).unwrap();
+        game.move_card(beast, ZoneType::Graveyard);
+        assert_eq!(game.graveyard.size(), 0);
+    }
+
+    #[test]
+    fn test_game_cast_planeswalker() {
+        let command_tower_data = CardData::make_command_tower_data();
+        let mut garruk_data = CardData::make_card_data("Garruk Wildspeaker", "Legendary Planeswalker — Garruk", "{2}{G}{G}");
+        garruk_data.loyalty = Some(3);
+        garruk_data.abilities = Some(vec![
+            Ability { trigger: Trigger::Activated, cost: Cost::Loyalty(1), effect: Effect::UntapLands(2), availability: 1.0, zones: ZoneType::Battlefield.into(), condition: None },
+        ]);
+
+        let mut game = Game::new();
+        for id in 1..5 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(6, &garruk_data));
+
+        // cast, and +1 right away to untap two of the towers
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        turn.main_phase(&settings());
+        assert_eq!(turn.game.hand.size(), 0);
+        assert_eq!(turn.game.battlefield.cards.iter().find(|c| c.id == 6).unwrap().counters(CounterType::Loyalty), 4);
+        assert_eq!(turn.mana_pool.cmc(), 6);
+    }
+
+    #[test]
+    fn test_game_counters() {
+        let elk_data = CardData::make_elk_data();
+        let sol_ring_data = CardData::make_sol_ring_data();
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &elk_data));
+        game.battlefield.add(Card::new_with_id(2, &elk_data));
+        game.battlefield.add(Card::new_with_id(3, &sol_ring_data));
+        game.battlefield.cards[0].add_counters(CounterType::PlusOne, 2);
+        game.battlefield.cards[1].add_counters(CounterType::MinusOne, 1);
+        game.battlefield.cards[2].add_counters(CounterType::Charge, 1);
+
+        let creatures = parse_card_filter("creature").unwrap();
+        let mut turn = Turn::new(&mut game, 1);
+        turn.proliferate();
+        assert_eq!(turn.game.battlefield.cards[0].counters(CounterType::PlusOne), 3);
+        assert_eq!(turn.game.battlefield.cards[1].counters(CounterType::MinusOne), 1);
+        assert_eq!(turn.game.battlefield.cards[2].counters(CounterType::Charge), 2);
+        assert_eq!(turn.count(&Amount::WithCounters(CounterType::PlusOne, creatures.clone())), 1);
+        assert_eq!(turn.count(&Amount::Counters(CounterType::PlusOne, creatures.clone())), 3);
+
+        // the biggest creature gets them
+        let source = turn.game.battlefield.cards[2].clone();
+        turn.add_counters(&source, CounterType::PlusOne, 1, &Some(creatures.clone()));
+        assert_eq!(turn.game.battlefield.cards[0].counters(CounterType::PlusOne), 4);
+        turn.add_counters(&source, CounterType::Charge, 1, &None);
+        assert_eq!(turn.game.battlefield.cards[2].counters(CounterType::Charge), 3);
+
+        turn.game.battlefield.cards[1].add_counters(CounterType::PlusOne, 2);
+        turn.move_counters(CounterType::PlusOne, 1, &creatures);
+        assert_eq!(turn.game.battlefield.cards[0].counters(CounterType::PlusOne), 5);
+        assert_eq!(turn.game.battlefield.cards[1].counters(CounterType::PlusOne), 0);
+        assert_eq!(turn.count(&Amount::WithCounters(CounterType::PlusOne, creatures)), 1);
+    }
+
+    #[test]
+    fn test_game_counters_from_hand() {
+        let plains_data = CardData::make_plains_data();
+        let command_tower_data = CardData::make_command_tower_data();
+        let ability = |trigger: Trigger, effect: Effect| Ability {
+            trigger,
+            cost: Cost::None,
+            effect,
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        };
+        let mut grakmaw_data = CardData::make_card_data("Grakmaw, Skyclave Ravager", "Legendary Creature — Hydra Horror", "{3}{B}{G}");
+        grakmaw_data.abilities = Some(vec![ability(Trigger::Cast, Effect::AddCounters { counter: CounterType::PlusOne, count: 3, filter: None })]);
+        let mut sage_data = CardData::make_card_data("Evolution Sage", "Creature — Elf Druid", "{2}{G}");
+        sage_data.abilities = Some(vec![ability(Trigger::ZoneChange {
+            from: None, to: Some(ZoneType::Battlefield), filter: parse_card_filter("land").unwrap()
+        }, Effect::Proliferate)]);
+
+        let mut game = Game::new();
+        for id in 1..9 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(10, &grakmaw_data));
+        game.hand.add(Card::new_with_id(11, &sage_data));
+
+        // the counters go on as it enters
+        {
+            let mut turn = Turn::new(&mut game, 1);
+            turn.untap_step();
+            turn.main_phase(&settings());
+            assert_eq!(turn.game.hand.size(), 0);
+        }
+        let counters = |game: &Game| game.battlefield.cards.iter().find(|card| card.id == 10).unwrap().counters(CounterType::PlusOne);
+        assert_eq!(counters(&game), 3);
+
+        // landfall, proliferate
+        game.hand.add(Card::new_with_id(12, &plains_data));
+        {
+            let mut turn = Turn::new(&mut game, 2);
+            turn.untap_step();
+            turn.main_phase(&settings());
+            assert_eq!(turn.lands_played, 1);
+        }
+        assert_eq!(counters(&game), 4);
+    }
+
+    #[test]
+    fn test_game_conditions() {
+        let plains_data = CardData::make_plains_data();
+        let elk_data = CardData::make_elk_data();
+        let mut mind_stone_data = CardData::make_sol_ring_data();
+        mind_stone_data.abilities.as_mut().unwrap()[0].condition = Some(crate::expr::parse("lands_on_battlefield >= 2 && hand_size <= 1").unwrap());
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+        game.battlefield.add(Card::new_with_id(2, &mind_stone_data));
+        game.hand.add(Card::new_with_id(3, &elk_data));
+        game.graveyard.add(Card::new_with_id(4, &elk_data));
+
+        let turn = Turn::new(&mut game, 1);
+        let mana_abilities = |turn: &Turn| turn.find_active_abilities(|ability| ability.effect.is_produce_mana()).len();
+        assert_eq!(mana_abilities(&turn), 1);
+        turn.game.battlefield.add(Card::new_with_id(5, &plains_data));
+        assert_eq!(mana_abilities(&turn), 3);
+        turn.game.hand.add(Card::new_with_id(6, &elk_data));
+        assert_eq!(mana_abilities(&turn), 2);
+
+        let expr = crate::expr::parse("count(graveyard, creature) + count(hand, creature) * 10 + turn").unwrap();
+        assert_eq!(expr.evaluate(&turn), 22);
+        assert_eq!(turn.count(&Amount::Expr(crate::expr::parse("count(land) - 5").unwrap())), 0);
+    }
+
+    #[test]
+    fn test_game_interaction() {
+        let plains_data = CardData::make_plains_data();
+        let elk_data = CardData::make_elk_data();
+        let sol_ring_data = CardData::make_sol_ring_data();
+
+        let mut game = Game::new();
+        game.interaction.removal = 1.0;
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+        game.battlefield.add(Card::new_with_id(2, &sol_ring_data));
+        let mut commander = Card::new_with_id(3, &elk_data);
+        commander.commander = true;
+        game.battlefield.add(commander);
+
+        // the commander is the best target, and goes back to the command zone
+        let mut turn = Turn::new(&mut game, 1);
+        turn.opponents_interact();
+        assert_eq!(turn.game.battlefield.cards.len(), 2);
+        assert_eq!(turn.game.command.cards[0].id, 3);
+        assert_eq!(turn.turn_stats.permanents_removed, 1);
+
+        turn.game.interaction = Interaction::none();
+        turn.game.interaction.artifact_wipe = 1.0;
+        turn.opponents_interact();
+        assert_eq!(turn.game.battlefield.cards.len(), 1);
+        assert_eq!(turn.game.graveyard.cards[0].id, 2);
+
+        // one counterspell a turn
+        turn.game.interaction.counterspell = 1.0;
+        turn.game.interaction.counterspell_min_cmc = 2;
+        turn.opponents_interact();
+        assert!(!turn.is_countered(&Card::new_with_id(4, &sol_ring_data)));
+        assert!(turn.is_countered(&Card::new_with_id(5, &elk_data)));
+        assert!(!turn.is_countered(&Card::new_with_id(6, &elk_data)));
+        assert_eq!(turn.turn_stats.spells_countered, 1);
+    }
+
+    #[test]
+    fn test_game_channel() {
+        let plains_data = CardData::make_plains_data();
+        let sol_ring_data = CardData::make_sol_ring_data();
+        let elk_data = CardData::make_elk_data();
+        let mut takenuma_data = CardData::make_swamp_data();
+        takenuma_data.abilities.get_or_insert(Vec::new()).push(Ability {
+            trigger: Trigger::Activated,
+            cost: Cost::Mana(ManaPool::new_from_string("{1}").unwrap()),
+            effect: Effect::ReturnFromGraveyard { filter: parse_card_filter("creature").unwrap(), destination: Destination::Hand, count: 1 },
+            availability: 1.0,
+            zones: ZoneType::Hand.into(),
+            condition: None
+        });
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &sol_ring_data));
+        game.hand.add(Card::new_with_id(2, &takenuma_data));
+        game.graveyard.add(Card::new_with_id(3, &elk_data));
+
+        // the land is kept while it is needed for a land drop
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        assert!(!turn.try_to_channel());
+        turn.lands_played = 1;
+        assert!(!turn.try_to_channel());
+        turn.game.hand.add(Card::new_with_id(4, &plains_data));
+        assert!(turn.try_to_channel());
+        assert_eq!(turn.game.hand.cards.iter().map(|c| c.id).collect::<Vec<u32>>(), vec![4, 3]);
+        assert_eq!(turn.game.graveyard.cards[0].id, 2);
+        assert_eq!(turn.mana_spent.cmc(), 1);
+    }
+
+    #[test]
+    fn test_game_empty_hand() {
+        let plains_data = CardData::make_plains_data();
+        let command_tower_data = CardData::make_command_tower_data();
+        let elk_data = CardData::make_elk_data();
+        let mut bloodbraid_data = CardData::make_card_data("Bloodbraid Elf", "Creature — Elf Berserker", "{2}{R}{G}");
+        bloodbraid_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Cast,
+            cost: Cost::None,
+            effect: Effect::Cascade,
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }]);
+
+        let mut game = Game::new();
+        for id in 1..6 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(10, &bloodbraid_data));
+        game.library.add(Card::new_with_id(20, &elk_data));
+        for id in 21..40 {
+            game.library.add(Card::new_with_id(id, &plains_data));
+        }
+
+        // cards with abilities no other try_to_xxx() is after get cast too
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        turn.main_phase(&settings());
+        assert_eq!(turn.game.hand.size(), 0);
+        assert!(turn.game.battlefield.cards.iter().any(|card| card.id == 10));
+        assert!(turn.game.battlefield.cards.iter().any(|card| card.id == 20));
+        assert_eq!(turn.turn_stats.cards_cast_free, 1);
+        assert_eq!(turn.game.library.size(), 19);
+        assert_eq!(turn.game.exile.size(), 0);
+    }
+
+    #[test]
+    fn test_game_cast_scry() {
+        let plains_data = CardData::make_plains_data();
+        let command_tower_data = CardData::make_command_tower_data();
+        let ulamog_data = CardData::make_card_data("Ulamog, the Ceaseless Hunger", "Legendary Creature — Eldrazi", "{10}");
+        let ability = |effect: Effect| Ability {
+            trigger: Trigger::Cast,
+            cost: Cost::None,
+            effect,
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        };
+        let mut omenspeaker_data = CardData::make_card_data("Omenspeaker", "Creature — Human Wizard", "{1}{U}");
+        omenspeaker_data.abilities = Some(vec![ability(Effect::Scry(2))]);
+        let mut doomed_data = CardData::make_card_data("Doomed Necromancer", "Creature — Human Cleric Mercenary", "{2}{B}");
+        doomed_data.abilities = Some(vec![ability(Effect::Surveil(2))]);
+
+        let mut game = Game::new();
+        for id in 1..6 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(10, &omenspeaker_data));
+        game.hand.add(Card::new_with_id(11, &doomed_data));
+        for id in 20..24 {
+            game.library.put_on_top(Card::new_with_id(id, &plains_data));
+        }
+        for id in 24..27 {
+            game.library.put_on_top(Card::new_with_id(id, &ulamog_data));
+        }
+
+        // surveil the top two into the graveyard, then scry the third
+        // to the bottom, with no land in hand the plains stays on top
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        turn.main_phase(&settings());
+        assert_eq!(turn.game.hand.size(), 0);
+        assert_eq!(turn.game.graveyard.size(), 2);
+        assert_eq!(turn.game.library.cards[0].id, 24);
+        assert_eq!(turn.game.library.take_top(1)[0].id, 23);
+    }
+
+    #[test]
+    fn test_game_cast_cycling_spell() {
+        let command_tower_data = CardData::make_command_tower_data();
+        let mut decree_data = CardData::make_card_data("Decree of Savagery", "Instant", "{7}{G}{G}");
+        decree_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Activated,
+            cost: Cost::Mana(ManaPool::new_from_string("{4}{G}{G}").unwrap()),
+            effect: Effect::Draw(vec![1]),
+            availability: 1.0,
+            zones: ZoneType::Hand.into(),
+            condition: None
+        }]);
+
+        let mut game = Game::new();
+        for id in 1..10 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(10, &decree_data));
+
+        // cast for its mana cost, not cycled
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        turn.main_phase(&settings());
+        assert_eq!(turn.game.hand.size(), 0);
+        assert_eq!(turn.turn_stats.cards_drawn, 0);
+        assert_eq!(turn.mana_spent.cmc(), 9);
+    }
+
+    #[test]
+    fn test_game_tutor_spells() {
+        let command_tower_data = CardData::make_command_tower_data();
+        let sol_ring_data = CardData::make_sol_ring_data();
+        let elk_data = CardData::make_elk_data();
+        let tutor = |name: &str, mana_cost: &str, filter: &str, destination: Destination, preferences: Vec<TutorPreference>| {
+            let mut data = CardData::make_card_data(name, "Sorcery", mana_cost);
+            data.abilities = Some(vec![Ability {
+                trigger: Trigger::Cast,
+                cost: Cost::None,
+                effect: Effect::Tutor { filter: parse_card_filter(filter).unwrap(), destination, preferences },
+                availability: 1.0,
+                zones: ZoneType::Battlefield.into(),
+                condition: None
+            }]);
+            return data;
+        };
+        let sol_ring = CardFilter { name: Some("Sol Ring".to_string()), ..CardFilter::any() };
+        let demonic_tutor_data = tutor("Demonic Tutor", "{1}{B}", "any", Destination::Hand, vec![TutorPreference::Matching(sol_ring)]);
+        let natural_order_data = tutor("Natural Order", "{2}{G}{G}", "creature", Destination::Battlefield, Vec::new());
+
+        let mut game = Game::new();
+        for id in 1..7 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(10, &demonic_tutor_data));
+        game.hand.add(Card::new_with_id(11, &natural_order_data));
+        game.library.add(Card::new_with_id(20, &sol_ring_data));
+        for id in 21..40 {
+            game.library.add(Card::new_with_id(id, &elk_data));
+        }
+        let library_order = |game: &Game| game.library.cards.iter().map(|card| card.id).collect::<Vec<u32>>();
+        let before = library_order(&game);
+
+        // the cheaper one first, the preferred card to hand, then shuffle
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        assert!(turn.try_to_play_tutor_spell());
+        assert!(turn.game.hand.cards.iter().any(|card| card.id == 20));
+        assert_eq!(turn.game.library.size(), 19);
+        let after = library_order(turn.game);
+        assert_ne!(after, before.into_iter().filter(|id| *id != 20).collect::<Vec<u32>>());
+
+        // a creature straight onto the battlefield
+        assert!(turn.try_to_play_tutor_spell());
+        assert_eq!(turn.game.library.size(), 18);
+        assert!(turn.game.battlefield.cards.iter().any(|card| card.is_type(Types::Creature)));
+        assert!(!turn.try_to_play_tutor_spell());
+    }
+
+    #[test]
+    fn test_game_ramp_spells() {
+        let swamp_data = CardData::make_swamp_data();
+        let mut black_market_data = CardData::make_card_data("Black Market", "Enchantment", "{3}{B}{B}");
+        black_market_data.abilities = Some(vec![Ability {
+            trigger: Trigger::BeginningOf(Phase::FirstMain),
+            cost: Cost::None,
+            effect: Effect::ProduceManaPer {
+                mana: ManaPool::new_from_string("{B}").unwrap(),
+                amount: Amount::Counters(CounterType::Charge, parse_card_filter("enchantment").unwrap())
+            },
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }]);
+
+        let mut game = Game::new();
+        for id in 1..6 {
+            game.battlefield.add(Card::new_with_id(id, &swamp_data));
+        }
+        game.hand.add(Card::new_with_id(10, &black_market_data));
+
+        // mana depending on the board is ramp too
+        {
+            let mut turn = Turn::new(&mut game, 1);
+            turn.untap_step();
+            assert!(turn.try_to_play_ramp_spell());
+            assert_eq!(turn.mana_spent.cmc(), 5);
+        }
+        game.battlefield.cards.iter_mut().find(|card| card.id == 10).unwrap().add_counters(CounterType::Charge, 2);
+
+        let mut turn = Turn::new(&mut game, 2);
+        turn.untap_step();
+        turn.begin_phase(Phase::FirstMain);
+        turn.trigger_phase_abilities();
+        assert_eq!(turn.mana_pool.cmc(), 7);
+        assert_eq!(turn.mana_pool.black, 7);
+    }
+
+    #[test]
+    fn test_game_mana_multiplier() {
+        let command_tower_data = CardData::make_command_tower_data();
+        let mut reflection_data = CardData::make_card_data("Mana Reflection", "Enchantment", "{4}{G}{G}");
+        reflection_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Static,
+            cost: Cost::None,
+            effect: Effect::ManaMultiplier { filter: CardFilter::any(), factor: 2 },
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }]);
+
+        let mut game = Game::new();
+        for id in 1..7 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(10, &reflection_data));
+
+        {
+            let mut turn = Turn::new(&mut game, 1);
+            turn.untap_step();
+            assert!(turn.try_to_play_ramp_spell());
+            assert_eq!(turn.game.battlefield.size(), 7);
+        }
+
+        // every land taps for twice the mana from the next turn on
+        let mut turn = Turn::new(&mut game, 2);
+        turn.untap_step();
+        assert_eq!(turn.mana_pool.cmc(), 12);
+    }
+
+    #[test]
+    fn test_game_cast_from_graveyard() {
+        let sol_ring_data = CardData::make_sol_ring_data();
+        let cast_from_graveyard = |name: &str, cost: &str, exile: u32| {
+            let mut data = CardData::make_card_data(name, "Instant", "{1}{U}");
+            data.abilities = Some(vec![Ability {
+                trigger: Trigger::Activated,
+                cost: Cost::Mana(ManaPool::new_from_string(cost).unwrap()),
+                effect: Effect::CastFromGraveyard { exile },
+                availability: 1.0,
+                zones: ZoneType::Graveyard.into(),
+                condition: None
+            }]);
+            return data;
+        };
+        let flashback_data = cast_from_graveyard("Think Twice", "{2}", 0);
+        let escape_data = cast_from_graveyard("Escape Velocity", "{1}", 5);
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &sol_ring_data));
+        game.graveyard.add(Card::new_with_id(2, &escape_data));
+        game.graveyard.add(Card::new_with_id(3, &flashback_data));
+
+        // escape lacks other cards to exile, flashback exiles the spell
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        assert!(turn.try_to_cast_from_graveyard());
+        assert_eq!(turn.game.exile.cards.iter().map(|c| c.id).collect::<Vec<u32>>(), vec![3]);
+        assert_eq!(turn.mana_spent.cmc(), 2);
+        assert!(!turn.try_to_cast_from_graveyard());
+
+        // a countered flashback spell is exiled all the same
+        turn.counterspell_held = true;
+        turn.cast_card(Card::new_with_id(4, &flashback_data), None, true);
+        assert_eq!(turn.turn_stats.spells_countered, 1);
+        assert_eq!(turn.game.exile.cards.iter().map(|c| c.id).collect::<Vec<u32>>(), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_game_mana_conversions() {
+        let plains_data = CardData::make_plains_data();
+        let mut signet_data = CardData::make_sol_ring_data();
+        signet_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Activated,
+            cost: Cost::TapMana(ManaPool::new_from_string("{1}").unwrap()),
+            effect: Effect::ProduceMana(ManaPool::new_from_string("{U}{B}").unwrap()),
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }]);
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+        game.battlefield.add(Card::new_with_id(2, &plains_data));
+        game.battlefield.add(Card::new_with_id(3, &signet_data));
+
+        // the signet isn't free mana, it converts one of the plains
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        assert_eq!(turn.mana_pool.cmc(), 2);
+        let cost = |text: &str| ManaPool::new_from_string(text).unwrap();
+        assert_eq!(turn.find_mana_conversions(&turn.mana_pool, &cost("{W}{W}")).map(|c| c.len()), Some(0));
+        assert_eq!(turn.find_mana_conversions(&turn.mana_pool, &cost("{W}{U}")).map(|c| c.len()), Some(1));
+        assert!(turn.can_afford(&cost("{B}{U}{W}")));
+        assert!(!turn.can_afford(&cost("{W}{W}{W}")));
+        assert!(!turn.can_afford(&cost("{U}{B}{B}")));
+
+        turn.convert_mana_for(&cost("{W}{W}"));
+        assert_eq!(turn.mana_pool.cmc(), 2);
+        turn.convert_mana_for(&cost("{W}{U}"));
+        assert_eq!(turn.mana_pool.cmc(), 4);
+        assert_eq!(turn.mana_spent.cmc(), 1);
+        assert!(turn.game.battlefield.cards[2].tapped);
+        assert!(turn.mana_pool.can_also_pay_for(&turn.mana_spent, &cost("{W}{U}")).is_some());
+    }
+
+    #[test]
+    fn test_game_mana_conversions_for_abilities() {
+        let plains_data = CardData::make_plains_data();
+        let mut signet_data = CardData::make_sol_ring_data();
+        signet_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Activated,
+            cost: Cost::TapMana(ManaPool::new_from_string("{1}").unwrap()),
+            effect: Effect::ProduceMana(ManaPool::new_from_string("{U}{B}").unwrap()),
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }]);
+        let mut cycler_data = CardData::make_card_data("Sphinx of the Cycle", "Creature — Sphinx", "{5}{U}{U}");
+        cycler_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Activated,
+            cost: Cost::Mana(ManaPool::new_from_string("{U}").unwrap()),
+            effect: Effect::Draw(vec![1]),
+            availability: 1.0,
+            zones: ZoneType::Hand.into(),
+            condition: None
+        }]);
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+        game.battlefield.add(Card::new_with_id(2, &signet_data));
+        game.hand.add(Card::new_with_id(3, &cycler_data));
+        game.library.add(Card::new_with_id(4, &plains_data));
+
+        // only blue through the signet
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        assert!(turn.try_to_cycle());
+        assert_eq!(turn.game.hand.cards[0].id, 4);
+        assert!(turn.game.battlefield.cards[1].tapped);
+        assert_eq!(turn.mana_spent.cmc(), 2);
+    }
+
+    #[test]
+    fn test_game_hate_pieces() {
+        let plains_data = CardData::make_plains_data();
+        let command_tower_data = CardData::make_command_tower_data();
+        let sol_ring_data = CardData::make_sol_ring_data();
+        let elk_data = CardData::make_elk_data();
+
+        let mut game = Game::new();
+        game.hate = vec![HatePiece::Thalia, HatePiece::RuleOfLaw, HatePiece::BloodMoon, HatePiece::CollectorOuphe];
+        game.battlefield.add(Card::new_with_id(1, &plains_data));
+        game.battlefield.add(Card::new_with_id(2, &command_tower_data));
+        game.battlefield.add(Card::new_with_id(3, &sol_ring_data));
+
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        assert_eq!(turn.mana_pool.cmc(), 2);
+        assert_eq!(turn.mana_pool.red, 1);
+
+        let sol_ring = Card::new_with_id(4, &sol_ring_data);
+        let elk = Card::new_with_id(5, &elk_data);
+        assert_eq!(turn.cost_of(&sol_ring, sol_ring_data.mana_cost.as_ref().unwrap()).cmc(), 2);
+        assert_eq!(turn.cost_of(&elk, elk_data.mana_cost.as_ref().unwrap()).cmc(), 3);
+
+        assert!(turn.can_cast_spell());
+        turn.spells_cast += 1;
+        assert!(!turn.can_cast_spell());
+
+        assert_eq!(parse_hate_piece("blood-moon"), Some(HatePiece::BloodMoon));
+        assert_eq!(parse_hate_piece("winter-orb"), None);
+    }
+
+    #[test]
+    fn test_game_table() {
+        let mut tithe_data = CardData::make_sol_ring_data();
+        let ability = |trigger: Trigger, effect: Effect| Ability {
+            trigger,
+            cost: Cost::None,
+            effect,
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        };
+        let counters = |counter: CounterType| Effect::AddCounters { counter, count: 1, filter: None };
+        tithe_data.abilities = Some(vec![
+            ability(Trigger::Opponent(TableEvent::Draw), counters(CounterType::PlusOne)),
+            ability(Trigger::Opponent(TableEvent::SpellCast), counters(CounterType::Charge)),
+            ability(Trigger::Opponent(TableEvent::SecondSpellCast), counters(CounterType::Oil)),
+            ability(Trigger::EachDrawStep, Effect::Draw(vec![2])),
+            ability(Trigger::EachDrawStep, Effect::NoMaximumHandSize),
+        ]);
+
+        let mut game = Game::new();
+        game.table = Table { opponents: 3, spells_per_turn: 2.0, creature_deaths_per_turn: 0.0 };
+        game.battlefield.add(Card::new_with_id(1, &tithe_data));
+
+        // everyone draws two extra cards, so three draws per opponent
+        let mut turn = Turn::new(&mut game, 1);
+        turn.opponents_play();
+        assert_eq!(turn.game.battlefield.cards[0].counters(CounterType::PlusOne), 9);
+        assert_eq!(turn.game.battlefield.cards[0].counters(CounterType::Charge), 6);
+        assert_eq!(turn.game.battlefield.cards[0].counters(CounterType::Oil), 3);
+        assert_eq!(crate::expr::parse("opponents * 2").unwrap().evaluate(&turn), 6);
+        assert!(Trigger::EachDrawStep.is_beginning_of(Phase::Draw));
+    }
+
+    #[test]
+    fn test_game_active_abilities_by_zone() {
+        let ability = |effect: Effect, zones: BitFlags<ZoneType>| Ability {
+            trigger: Trigger::Static,
+            cost: Cost::None,
+            effect,
+            availability: 1.0,
+            zones,
+            condition: None
+        };
+        let mut eminence_data = CardData::make_elk_data();
+        eminence_data.abilities = Some(vec![
+            ability(Effect::NoMaximumHandSize, ZoneType::Battlefield | ZoneType::Command),
+            ability(Effect::LandLimit(1), ZoneType::Battlefield.into())
+        ]);
+        let mut graveyard_data = CardData::make_elk_data();
+        graveyard_data.abilities = Some(vec![ability(Effect::LandLimit(1), ZoneType::Graveyard.into())]);
+
+        let mut game = Game::new();
+        game.command.add(Card::new_with_id(1, &eminence_data));
+        game.hand.add(Card::new_with_id(2, &eminence_data));
+        game.hand.add(Card::new_with_id(3, &graveyard_data));
+
+        let mut turn = Turn::new(&mut game, 1);
+        let active = |turn: &Turn| turn.find_active_abilities(|ability| ability.trigger.is_static())
+            .iter()
+            .map(|(card, ability)| format!("{}:{}", card.id, ability.effect))
+            .collect::<Vec<String>>();
+        assert_eq!(active(&turn), vec!["1:no-max-hand-size"]);
+
+        let card = turn.game.hand.take(2).unwrap();
+        turn.move_card(card, ZoneType::Battlefield);
+        let card = turn.game.hand.take(3).unwrap();
+        turn.move_card(card, ZoneType::Graveyard);
+        assert_eq!(active(&turn), vec!["2:no-max-hand-size", "2:land-limit=+1", "3:land-limit=+1", "1:no-max-hand-size"]);
+        turn.update_static_effects();
+        assert!(turn.statics.no_maximum_hand_size);
+        assert_eq!(turn.statics.land_limit, 2);
+
+        // activated abilities only work from the battlefield here
+        let mut draw_data = CardData::make_elk_data();
+        draw_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Activated,
+            cost: Cost::Sacrifice,
+            effect: Effect::Draw(vec![1]),
+            availability: 1.0,
+            zones: ZoneType::Command | ZoneType::Graveyard,
+            condition: None
+        }]);
+        turn.game.command.add(Card::new_with_id(4, &draw_data));
+        turn.game.graveyard.add(Card::new_with_id(5, &draw_data));
+        assert!(!turn.try_to_activate_draw_ability());
+        assert_eq!(turn.game.command.size(), 2);
+    }
+
+    #[test]
+    fn test_game_static_effects() {
+        let plains_data = CardData::make_plains_data();
+        let mut azusa_data = CardData::make_elk_data();
+        let ability = |effect: Effect| Ability {
+            trigger: Trigger::Static,
+            cost: Cost::None,
+            effect,
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        };
+        azusa_data.abilities = Some(vec![ability(Effect::LandLimit(2)), ability(Effect::PlayLandsFromLibraryTop)]);
+
+        let mut game = Game::new();
+        game.move_card(Card::new_with_id(1, &azusa_data), ZoneType::Battlefield);
+        game.library.add(Card::new_with_id(2, &plains_data));
+        game.library.add(Card::new_with_id(3, &plains_data));
+        game.hand.add(Card::new_with_id(4, &plains_data));
+
+        // the extra land drops last beyond the turn it arrived
+        for turn_number in 2..4 {
+            let mut turn = Turn::new(&mut game, turn_number);
+            turn.update_static_effects();
+            assert_eq!(turn.land_limit(), 3);
+            assert!(turn.statics.lands_from_library_top);
+        }
+
+        let mut turn = Turn::new(&mut game, 4);
+        turn.update_static_effects();
+        assert!(turn.try_to_play_land());
+        assert_eq!(turn.game.library.size(), 1);
+        assert_eq!(turn.game.hand.size(), 1);
+
+        let azusa = turn.game.battlefield.take(1).unwrap();
+        turn.move_card(azusa, ZoneType::Graveyard);
+        assert_eq!(turn.land_limit(), 1);
+        assert!(!turn.try_to_play_land());
+    }
+
+    #[test]
+    fn test_game_cast_static_effects() {
+        let plains_data = CardData::make_plains_data();
+        let command_tower_data = CardData::make_command_tower_data();
+        let mut courser_data = CardData::make_card_data("Courser of Kruphix", "Enchantment Creature — Centaur", "{1}{G}{G}");
+        courser_data.abilities = Some(vec![Ability {
+            trigger: Trigger::Static,
+            cost: Cost::None,
+            effect: Effect::PlayLandsFromLibraryTop,
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }]);
+
+        let mut game = Game::new();
+        for id in 1..4 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(4, &courser_data));
+        game.library.add(Card::new_with_id(5, &plains_data));
+
+        // cast, then the land drop comes from the top of the library
+        let mut turn = Turn::new(&mut game, 1);
+        turn.untap_step();
+        turn.main_phase(&settings());
+        assert_eq!(turn.game.hand.size(), 0);
+        assert_eq!(turn.game.library.size(), 0);
+        assert_eq!(turn.game.battlefield.size(), 5);
+        assert_eq!(turn.lands_played, 1);
+    }
+
+    #[test]
+    fn test_game_cost_of() {
+        let sol_ring_data = CardData::make_sol_ring_data();
+        let elk_data = CardData::make_elk_data();
+        let ability = |effect: Effect, zone: ZoneType| Ability {
+            trigger: Trigger::Static,
+            cost: Cost::None,
+            effect,
+            availability: 1.0,
+            zones: zone.into(),
+            condition: None
+        };
+        let number = |n: i64| Amount::Expr(crate::expr::Expr::Number(n));
+        let mut incubator_data = CardData::make_sol_ring_data();
+        incubator_data.abilities = Some(vec![ability(Effect::CostReduction {
+            filter: parse_card_filter("creature").unwrap(), amount: number(1), floor: 0
+        }, ZoneType::Battlefield)]);
+        let mut commander_data = CardData::make_elk_data();
+        commander_data.abilities = Some(vec![ability(Effect::CostIncrease {
+            filter: CardFilter::any(), count: 1
+        }, ZoneType::Command)]);
+        let mut affinity_data = CardData::make_elk_data();
+        affinity_data.abilities = Some(vec![ability(Effect::CostReduction {
+            filter: CardFilter::any(), amount: Amount::Count(parse_card_filter("artifact").unwrap()), floor: 2
+        }, ZoneType::Hand)]);
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &incubator_data));
+        game.battlefield.add(Card::new_with_id(2, &sol_ring_data));
+        game.command.add(Card::new_with_id(3, &commander_data));
+
+        let turn = Turn::new(&mut game, 1);
+        let elk = Card::new_with_id(4, &elk_data);
+        assert_eq!(turn.cost_of(&elk, elk_data.mana_cost.as_ref().unwrap()).cmc(), 3);
+        let sol_ring = Card::new_with_id(5, &sol_ring_data);
+        assert_eq!(turn.cost_of(&sol_ring, sol_ring_data.mana_cost.as_ref().unwrap()).cmc(), 2);
+        // {3}{G} with three off, but not below the floor of two
+        let affinity = Card::new_with_id(6, &affinity_data);
+        assert_eq!(turn.cost_of(&affinity, affinity_data.mana_cost.as_ref().unwrap()).cmc(), 2);
+        // the commander's own increase doesn't apply to itself
+        let commander = turn.game.command.cards[0].clone();
+        assert_eq!(turn.cost_cmc(&commander), 2);
+    }
+
+    #[test]
+    fn test_game_phase_triggers() {
+        let mut clock_data = CardData::make_sol_ring_data();
+        clock_data.abilities = Some(PHASES.iter().map(|phase| Ability {
+            trigger: Trigger::BeginningOf(*phase),
+            cost: Cost::None,
+            effect: Effect::AddCounters { counter: CounterType::Charge, count: 1, filter: None },
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        }).collect());
+
+        let mut game = Game::new();
+        game.battlefield.add(Card::new_with_id(1, &clock_data));
+        Turn::new(&mut game, 1).play(&settings());
+        assert_eq!(game.battlefield.cards[0].counters(CounterType::Charge), 8);
+    }
+
+    #[test]
+    fn test_game_cast_cost_modifiers() {
+        let command_tower_data = CardData::make_command_tower_data();
+        let ability = |effect: Effect| Ability {
+            trigger: Trigger::Static,
+            cost: Cost::None,
+            effect,
+            availability: 1.0,
+            zones: ZoneType::Battlefield.into(),
+            condition: None
+        };
+        let chosen_type = CardFilter { tag: Some("chosen-type".to_string()), ..parse_card_filter("creature").unwrap() };
+        let mut horn_data = CardData::make_card_data("Herald's Horn", "Artifact", "{5}");
+        horn_data.abilities = Some(vec![ability(Effect::CostReduction {
+            filter: chosen_type, amount: Amount::Expr(crate::expr::Expr::Number(1)), floor: 0
+        })]);
+        let mut sphere_data = CardData::make_card_data("Sphere of Resistance", "Artifact", "{2}");
+        sphere_data.abilities = Some(vec![ability(Effect::CostIncrease { filter: CardFilter::any(), count: 1 })]);
+        let mut elk_data = CardData::make_elk_data();
+        elk_data.tags = vec!["chosen-type".to_string()];
+        let mut commander_data = CardData::make_card_data("Elk Lord", "Legendary Creature — Elk", "{G}{G}");
+        commander_data.tags = vec!["chosen-type".to_string()];
+
+        let mut game = Game::new();
+        for id in 1..8 {
+            game.battlefield.add(Card::new_with_id(id, &command_tower_data));
+        }
+        game.hand.add(Card::new_with_id(10, &horn_data));
+        game.hand.add(Card::new_with_id(11, &elk_data));
+
+        // the elk only fits in for {1}{G}
+        {
+            let mut turn = Turn::new(&mut game, 1);
+            turn.untap_step();
+            turn.main_phase(&settings());
+            assert_eq!(turn.game.hand.size(), 0);
+        }
+
+        game.hand.add(Card::new_with_id(12, &sphere_data));
+        game.hand.add(Card::new_with_id(13, &elk_data));
+        {
+            let mut turn = Turn::new(&mut game, 2);
+            turn.untap_step();
+            turn.main_phase(&settings());
+            assert_eq!(turn.game.hand.size(), 0);
+            assert_eq!(turn.cost_cmc(&Card::new_with_id(14, &elk_data)), 3);
+        }
+
+        // the tax is part of the cost reductions apply to, {2}{G}{G} with one
+        // less for the horn
+        game.command.add(Card::new_with_id(15, &commander_data));
+        game.game_stats.commander_casts = 1;
+        let mut turn = Turn::new(&mut game, 3);
+        let sphere = turn.game.battlefield.take(12).unwrap();
+        turn.move_card(sphere, ZoneType::Graveyard);
+        turn.untap_step();
+        turn.mana_pool = ManaPool::new();
+        for _ in 0..3 {
+            turn.mana_pool.add_mana(&ALL);
+        }
+        assert!(turn.try_to_play_commander());
+    }
+
 }
 
 // Rules for playing land:
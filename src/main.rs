@@ -5,6 +5,7 @@ mod game;
 mod zone;
 mod oracle;
 mod stats;
+mod expr;
 
 use std::io::BufRead;
 use regex::Regex;
@@ -14,6 +15,7 @@ use clap::Parser;
 struct DeckListEntry {
     count : u32,
     name : String,
+    tags : Vec<String>,
 }
 
 fn read_deck_list(file_name : &str) -> Result<Vec<DeckListEntry>, String> {
@@ -21,14 +23,24 @@ fn read_deck_list(file_name : &str) -> Result<Vec<DeckListEntry>, String> {
     let file = std::fs::File::open(file_name).unwrap();
     let lines = std::io::BufReader::new(file).lines();
     let re = Regex::new(r"^(\d+)x?\s+([\w\s',\-\\/]+)").unwrap();
+    let tags_re = Regex::new(r"\[([^\]]*)\]").unwrap();
     for line in lines.filter(|l| l.is_ok()).map(|l| l.unwrap()) {
         match re.captures(&line) {
             Some(captures) => {
                 let count = captures.get(1).unwrap().as_str().parse::<u32>().unwrap();
                 let name = captures.get(2).unwrap().as_str();
+                let tags = match tags_re.captures(&line) {
+                    Some(tag_captures) => tag_captures[1]
+                        .split(',')
+                        .map(|t| t.trim().to_lowercase())
+                        .filter(|t| !t.is_empty())
+                        .collect(),
+                    None => Vec::new()
+                };
                 deck_list.push(DeckListEntry {
                     count: count,
-                    name: name.trim().to_lowercase()
+                    name: name.trim().to_lowercase(),
+                    tags
                 })
             },
             None => {
@@ -39,6 +51,52 @@ fn read_deck_list(file_name : &str) -> Result<Vec<DeckListEntry>, String> {
     return Ok(deck_list);
 }
 
+fn parse_format(txt : &Option<String>) -> game::Format {
+    match txt {
+        Some(text) => match text.as_str() {
+            "commander" => game::Format::Commander,
+            "constructed" => game::Format::Constructed,
+            _ => panic!("invalid format specified, only 'commander' and 'constructed' are available..")
+        },
+        None => game::Format::Commander
+    }
+}
+
+fn parse_loyalty(txt : &Option<String>) -> game::LoyaltyPolicy {
+    match txt {
+        Some(text) => match text.as_str() {
+            "grow" => game::LoyaltyPolicy::Grow,
+            "spend" => game::LoyaltyPolicy::Spend,
+            _ => panic!("invalid loyalty policy specified, only 'grow' and 'spend' are available..")
+        },
+        None => game::LoyaltyPolicy::Grow
+    }
+}
+
+fn parse_interaction(txt : &Option<String>) -> game::Interaction {
+    match txt {
+        Some(text) => match text.as_str() {
+            "none" => game::Interaction::none(),
+            "casual" => game::Interaction::casual(),
+            "competitive" => game::Interaction::competitive(),
+            _ => panic!("invalid interaction specified, only 'none', 'casual' and 'competitive' are available..")
+        },
+        None => game::Interaction::none()
+    }
+}
+
+fn parse_hate(txt : &Option<String>) -> Vec<game::HatePiece> {
+    match txt {
+        Some(text) => text.split(',')
+            .map(|piece| match game::parse_hate_piece(piece.trim()) {
+                Some(piece) => piece,
+                None => panic!("invalid hate piece '{}', only 'thalia', 'rule-of-law', 'blood-moon', 'collector-ouphe' and 'archon-of-emeria' are available..", piece)
+            })
+            .collect(),
+        None => Vec::new()
+    }
+}
+
 fn parse_mulligan(txt : &Option<String>) -> game::MulliganType {
     match txt {
         Some(text) => match text.as_str() {
@@ -75,7 +133,52 @@ struct Arguments {
     verbose_game : bool,
 
     #[arg(long)]
-    mulligan : Option<String>
+    mulligan : Option<String>,
+
+    #[arg(long)]
+    format : Option<String>,
+
+    #[arg(long, default_value_t = 10)]
+    min_life : i32,
+
+    #[arg(long)]
+    loyalty : Option<String>,
+
+    #[arg(long, default_value_t = 3)]
+    opponents : u32,
+
+    #[arg(long, default_value_t = 1.5)]
+    opponent_spells : f32,
+
+    #[arg(long, default_value_t = 0.5)]
+    creature_deaths : f32,
+
+    #[arg(long)]
+    interaction : Option<String>,
+
+    #[arg(long)]
+    removal : Option<f32>,
+
+    #[arg(long)]
+    creature_wipe : Option<f32>,
+
+    #[arg(long)]
+    artifact_wipe : Option<f32>,
+
+    #[arg(long)]
+    full_wipe : Option<f32>,
+
+    #[arg(long)]
+    counterspell : Option<f32>,
+
+    #[arg(long)]
+    counterspell_min_cmc : Option<u32>,
+
+    #[arg(long)]
+    hate : Option<String>,
+
+    #[arg(long)]
+    chosen_type : Option<String>,
 }
 
 fn main() {
@@ -112,6 +215,19 @@ fn main() {
         panic!("commander {} was not found in the decklist...", args.commander);
     }
 
+    deck_list.iter().for_each(|e| db.add_tags(&e.name, &e.tags));
+
+    // The creature type chosen for cards like 'Herald's Horn', cards can also
+    // be tagged 'chosen-type' in the deck list.
+    if let Some(chosen_type) = &args.chosen_type {
+        let chosen_tag = vec!["chosen-type".to_string()];
+        let chosen : Vec<String> = deck_list.iter()
+            .filter(|e| db.entries[&e.name].has_subtype(chosen_type))
+            .map(|e| e.name.clone())
+            .collect();
+        chosen.iter().for_each(|name| db.add_tags(name, &chosen_tag));
+    }
+
     let mut stem_game = game::Game::new();
     if args.verbose_game {
         stem_game.verbose = true;
@@ -119,9 +235,11 @@ fn main() {
 
     deck_list.iter().for_each(|e| {
         let card_data = &db.entries[&e.name];
-        let card = card::Card::new(&card_data);
+        let mut card = card::Card::new(card_data);
         if card_data.name == args.commander {
             assert_eq!(e.count, 1);
+            card.zone = zone::ZoneType::Command;
+            card.commander = true;
             stem_game.command.add(card);
         } else {
             for _ in 0..e.count {
@@ -130,10 +248,30 @@ fn main() {
         }
     });
 
+    // The rates of the interaction preset can be set one by one.
+    let mut interaction = parse_interaction(&args.interaction);
+    interaction.removal = args.removal.unwrap_or(interaction.removal);
+    interaction.creature_wipe = args.creature_wipe.unwrap_or(interaction.creature_wipe);
+    interaction.artifact_wipe = args.artifact_wipe.unwrap_or(interaction.artifact_wipe);
+    interaction.full_wipe = args.full_wipe.unwrap_or(interaction.full_wipe);
+    interaction.counterspell = args.counterspell.unwrap_or(interaction.counterspell);
+    interaction.counterspell_min_cmc = args.counterspell_min_cmc.unwrap_or(interaction.counterspell_min_cmc);
+
     let settings = game::Settings {
         turn_count: args.rounds,
         draw_card_on_turn_one: true,
         mulligan : parse_mulligan(&args.mulligan),
+        max_hand_size: 7,
+        format: parse_format(&args.format),
+        min_life: args.min_life,
+        loyalty: parse_loyalty(&args.loyalty),
+        table: game::Table {
+            opponents: args.opponents,
+            spells_per_turn: args.opponent_spells,
+            creature_deaths_per_turn: args.creature_deaths,
+        },
+        interaction,
+        hate: parse_hate(&args.hate),
     };
 
     let mut stats : Vec<game::GameStats> = Vec::new();
@@ -45,6 +45,18 @@ pub const WHITE : Mana      = Mana { colors: enumflags2::make_bitflags!(Color::{
 #[allow(dead_code)]
 pub const ALL : Mana        = Mana { colors: enumflags2::make_bitflags!(Color::{ Black | Blue | Green | Red | White }) };
 
+// Single letters, like in mana costs.
+pub fn parse_color(text : &str) -> Option<Color> {
+    match text {
+        "B" => Some(Color::Black),
+        "U" => Some(Color::Blue),
+        "G" => Some(Color::Green),
+        "R" => Some(Color::Red),
+        "W" => Some(Color::White),
+        _ => None
+    }
+}
+
 impl std::fmt::Display for Mana {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let mut colors = Vec::new();
@@ -331,6 +343,28 @@ impl ManaPool {
             };
     }
 
+    /// Returns the pool repeated 'factor' times.
+    pub fn multiplied(&self, factor : u32) -> ManaPool {
+        let mut pool = ManaPool::new();
+        for _i in 0..factor {
+            pool.add_pool(self);
+        }
+        return pool;
+    }
+
+    /// Returns the number of mana symbols of the given color, hybrid symbols
+    /// included.
+    pub fn devotion(&self, color : Color) -> u32 {
+        let mono = match color {
+            Color::Black => self.black,
+            Color::Blue => self.blue,
+            Color::Green => self.green,
+            Color::Red => self.red,
+            Color::White => self.white,
+        };
+        return mono + self.multi.iter().flatten().filter(|m| m.contains(color)).count() as u32;
+    }
+
     pub fn expanded(&self, other : &ManaPool) -> ManaPool {
         let mut pool = self.clone();
         pool.add_pool(other);
@@ -457,6 +491,19 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_mana_pool_multiplied_and_devotion() {
+        let pool = ManaPool::new_from_string("{1}{B}{B/G}").unwrap();
+        let tripled = pool.multiplied(3);
+        assert_eq!(tripled.cmc(), 9);
+        assert_eq!(tripled.black, 3);
+        assert_eq!(tripled.colorless, 3);
+        assert_eq!(tripled.devotion(Color::Black), 6);
+        assert_eq!(tripled.devotion(Color::Green), 3);
+        assert_eq!(tripled.devotion(Color::Red), 0);
+        assert_eq!(pool.multiplied(0).cmc(), 0);
+    }
+
     #[test]
     fn test_mana_can_pay_for() {
         assert!(COLORLESS.can_pay_for(&COLORLESS));
@@ -1,23 +1,46 @@
 use crate::mana;
 use crate::card;
+use crate::zone;
 use lazy_static::lazy_static;
 
 use regex::Regex;
+use enumflags2::BitFlags;
 
 pub struct Context<'a> {
     pub text: &'a str,
     pub card_name: &'a str
 }
 
+// Rewrites the oracle text so that all references to the card itself read
+// '~', whether it is by name, short name or "this land" and the like, and
+// so that "enters the battlefield" from before the 2024 oracle update reads
+// "enters" like it does now.
+pub fn normalize(ctx: &Context) -> String {
+    lazy_static! {
+        static ref THIS: Regex = Regex::new(r"\b[Tt]his (land|creature|artifact|enchantment|permanent|planeswalker|card|spell)\b").unwrap();
+    }
+    let mut text = ctx.text.to_string();
+    if !ctx.card_name.is_empty() {
+        text = text.replace(ctx.card_name, "~");
+    }
+    if let Some((short_name, _)) = ctx.card_name.split_once(", ") {
+        let short_name_regex = Regex::new(&format!(r"\b{}\b", regex::escape(short_name))).unwrap();
+        text = short_name_regex.replace_all(&text, "~").to_string();
+    }
+    text = THIS.replace_all(&text, "~").to_string();
+    return text.replace(" enters the battlefield", " enters");
+}
+
 pub fn parse(ctx: &Context) -> Option<Vec<card::Ability>> {
 
+    let text = normalize(ctx);
     let mut abilities: Vec<card::Ability> = Vec::new();
 
     let mut is_mana_producer = false;
     let mut is_sac_for_cards = false;
 
     // Parse activated abilities
-    for (lhs, rhs) in ctx.text.split("\n")
+    for (lhs, rhs) in text.split("\n")
         .filter(|line| line.contains(":"))
         .map(|line| {
             let v : Vec<&str> = line.splitn(2, ":").collect();
@@ -46,12 +69,47 @@ pub fn parse(ctx: &Context) -> Option<Vec<card::Ability>> {
             trigger: card::Trigger::Activated,
             availability: 1.0,
             cost: cost.unwrap(),
-            effect: effect.unwrap()
+            effect: effect.unwrap(),
+            zones: zone::ZoneType::Battlefield.into(),
+            condition: None
         };
 
         abilities.push(ability);
     }
 
+    // Parse spell effects and enters the battlefield triggers
+    for line in text.split("\n").map(|l| l.trim()).filter(|l| !l.contains(":")) {
+        for effect in parse_cast_effects(line, ctx) {
+            abilities.push(card::Ability {
+                trigger: card::Trigger::Cast,
+                availability: 1.0,
+                cost: card::Cost::None,
+                effect,
+                zones: zone::ZoneType::Battlefield.into(),
+                condition: None
+            });
+        }
+    }
+
+    // Parse static abilities, like cost reductions
+    for line in text.split("\n").map(|l| l.trim()).filter(|l| !l.contains(":")) {
+        abilities.extend(parse_static_abilities(line));
+    }
+
+    // Parse abilities that are activated from hand
+    for line in text.split("\n").map(|l| l.trim()) {
+        if let Some(ability) = parse_hand_ability(line) {
+            abilities.push(ability);
+        }
+    }
+
+    // Parse casting from the graveyard, like flashback and escape
+    for line in text.split("\n").map(|l| l.trim()) {
+        if let Some(ability) = parse_graveyard_cast(line) {
+            abilities.push(ability);
+        }
+    }
+
     if is_mana_producer && is_sac_for_cards {
         abilities.iter_mut()
             .filter(|a| a.effect.is_draw())
@@ -64,29 +122,40 @@ pub fn parse(ctx: &Context) -> Option<Vec<card::Ability>> {
     }
 }
 
-fn parse_cost(cost_string: &str, ctx: &Context) -> Result<Option<card::Cost>, String> {
+fn parse_cost(cost_string: &str, _ctx: &Context) -> Result<Option<card::Cost>, String> {
     lazy_static! {
         static ref TAP: Regex = Regex::new(r"^\{T\}$").unwrap();
         static ref TAP_MANA: Regex = Regex::new(r"^(\{.*\}), \{T\}$").unwrap();
-        static ref SACRIFICE: Regex = Regex::new(r"^Sacrifice (.+)$").unwrap();
-        static ref TAP_MANA_SACRIFICE: Regex = Regex::new(r"^\{(.+)\}, \{T\}, Sacrifice (.+)$").unwrap();
+        static ref SACRIFICE: Regex = Regex::new(r"^Sacrifice ~$").unwrap();
+        static ref TAP_MANA_SACRIFICE: Regex = Regex::new(r"^\{(.+)\}, \{T\}, Sacrifice ~$").unwrap();
+        static ref LIFE: Regex = Regex::new(r"^Pay (\d+) life$").unwrap();
+        static ref TAP_LIFE: Regex = Regex::new(r"^\{T\}, Pay (\d+) life$").unwrap();
+        static ref TAP_LIFE_SACRIFICE: Regex = Regex::new(r"^\{T\}, Pay (\d+) life, Sacrifice ~$").unwrap();
+        static ref LOYALTY: Regex = Regex::new(r"^([+−-]?)(\d+)$").unwrap();
     }
     if TAP.is_match(cost_string) {
         return Ok(Some(card::Cost::Tap));
-    } else if let Some(cap) = SACRIFICE.captures(cost_string) {
-        if &cap[1] == ctx.card_name {
-            return Ok(Some(card::Cost::Sacrifice));
-        }
+    } else if SACRIFICE.is_match(cost_string) {
+        return Ok(Some(card::Cost::Sacrifice));
     } else if let Some(cap) = TAP_MANA.captures(cost_string) {
         let mana = mana::ManaPool::new_from_string(&cap[1])?;
         return Ok(Some(card::Cost::TapMana(mana)));
 
     } else if let Some(cap) = TAP_MANA_SACRIFICE.captures(cost_string) {
-        if &cap[2] != ctx.card_name {
-            return Ok(None);
-        }
         let mana = mana::ManaPool::new_from_string(&cap[1])?;
         return Ok(Some(card::Cost::TapManaSacrifice(mana)));
+    } else if let Some(cap) = LIFE.captures(cost_string) {
+        return Ok(Some(card::Cost::Life(cap[1].parse().unwrap())));
+    } else if let Some(cap) = TAP_LIFE.captures(cost_string) {
+        return Ok(Some(card::Cost::TapLife(cap[1].parse().unwrap())));
+    } else if let Some(cap) = TAP_LIFE_SACRIFICE.captures(cost_string) {
+        return Ok(Some(card::Cost::TapSacrificeLife(cap[1].parse().unwrap())));
+    } else if let Some(cap) = LOYALTY.captures(cost_string) {
+        let loyalty : i32 = cap[2].parse().unwrap();
+        return match &cap[1] {
+            "+" | "" => Ok(Some(card::Cost::Loyalty(loyalty))),
+            _ => Ok(Some(card::Cost::Loyalty(-loyalty)))
+        };
     }
 
     return Ok(None);
@@ -98,10 +167,14 @@ fn parse_effect(effect_string: &str, _ctx: &Context) -> Result<Option<card::Effe
         static ref ADD_MANA_X: Regex = Regex::new(r"^Add ((\{\w\})+)\.").unwrap();
         static ref ADD_MANA_X_OR_Y: Regex = Regex::new(r"^Add \{(\w)\} or \{(\w)\}.").unwrap();
         static ref ADD_MANA_X_Y_OR_Z: Regex = Regex::new(r"^Add \{(\w)\}, \{(\w)\}, or \{(\w)\}.").unwrap();
+        static ref ADD_MANA_PAIRS: Regex = Regex::new(r"^Add \{(\w)\}\{\w\}, \{\w\}\{\w\}, or \{\w\}\{(\w)\}\.").unwrap();
+        static ref ADD_MANA_ANY_COLOR: Regex = Regex::new(r"^Add one mana of any color\.").unwrap();
         static ref ADD_MANA_COMMANDER: Regex = Regex::new("Add one mana of any color in your commander's color identity.").unwrap();
         static ref DRAW_A_CARD: Regex = Regex::new("^Draw a card.").unwrap();
         static ref DRAW_TWO_CARDS: Regex = Regex::new("^Draw two cards.").unwrap();
         static ref DRAW_THREE_CARDS: Regex = Regex::new("^Draw three cards.").unwrap();
+        static ref UNTAP_LANDS: Regex = Regex::new(r"^Untap (up to )?(\w+) target lands?\.$").unwrap();
+        static ref CREATE_TOKEN: Regex = Regex::new(r"^Create (a|an|\w+) (\d+/\d+ )?([\w ]+?) creature tokens?\.$").unwrap();
     }
 
     if let Some(cap) = ADD_MANA_X.captures(effect_string) {
@@ -121,7 +194,14 @@ fn parse_effect(effect_string: &str, _ctx: &Context) -> Result<Option<card::Effe
         mana.set_from_string(&cap[3])?;
         return Ok(Some(card::Effect::ProduceMana(mana::ManaPool::new_from_single(&mana))));
 
-    } else if ADD_MANA_COMMANDER.is_match(effect_string) {
+    } else if let Some(cap) = ADD_MANA_PAIRS.captures(effect_string) {
+        // Filter lands, like "Add {U}{U}, {U}{B}, or {B}{B}."
+        let mut mana = mana::Mana::new();
+        mana.set_from_string(&cap[1])?;
+        mana.set_from_string(&cap[2])?;
+        return Ok(Some(card::Effect::ProduceMana(mana::ManaPool::new_from_single(&mana).multiplied(2))));
+
+    } else if ADD_MANA_ANY_COLOR.is_match(effect_string) || ADD_MANA_COMMANDER.is_match(effect_string) {
         return Ok(Some(card::Effect::ProduceMana(mana::ManaPool::new_from_single(&mana::ALL))));
 
     } else if DRAW_A_CARD.is_match(effect_string) {
@@ -132,28 +212,291 @@ fn parse_effect(effect_string: &str, _ctx: &Context) -> Result<Option<card::Effe
 
     } else if DRAW_THREE_CARDS.is_match(effect_string) {
         return Ok(Some(card::Effect::Draw(vec![3])));
+
+    } else if let Some(effect) = parse_counters_effect(effect_string) {
+        return Ok(Some(effect));
+
+    } else if let Some(cap) = UNTAP_LANDS.captures(effect_string) {
+        if let Some(count) = parse_number(&cap[2]) {
+            return Ok(Some(card::Effect::UntapLands(count)));
+        }
+
+    } else if let Some(cap) = CREATE_TOKEN.captures(effect_string) {
+        let count = match &cap[1] {
+            "a" | "an" => Some(1),
+            number => parse_number(number)
+        };
+        // The capitalized words are the creature types, the others colors.
+        let subtypes : Vec<&str> = cap[3].split(' ').filter(|w| w.starts_with(char::is_uppercase)).collect();
+        if let (Some(count), false) = (count, subtypes.is_empty()) {
+            let name = format!("{} Token", subtypes.join(" "));
+            let type_string = format!("Token Creature — {}", subtypes.join(" "));
+            return Ok(Some(card::Effect::CreateToken {
+                token: Box::new(card::CardData::new_token(&name, &type_string)),
+                count
+            }));
+        }
     }
 
     return Ok(None);
 }
 
+// Putting counters on permanents, and proliferate.
+fn parse_counters_effect(text: &str) -> Option<card::Effect> {
+    lazy_static! {
+        static ref PROLIFERATE: Regex = Regex::new(r"^Proliferate\.").unwrap();
+        static ref PUT_COUNTERS: Regex = Regex::new(r"^[Pp]ut (a|an|\w+) (\+1/\+1|-1/-1|loyalty|charge|oil) counters? on (~|target creature|target creature you control|target permanent)\.$").unwrap();
+    }
+
+    if PROLIFERATE.is_match(text) {
+        return Some(card::Effect::Proliferate);
+    } else if let Some(cap) = PUT_COUNTERS.captures(text) {
+        let count = match &cap[1] {
+            "a" | "an" => Some(1),
+            number => parse_number(number)
+        }?;
+        let filter = match &cap[3] {
+            "~" => None,
+            "target permanent" => Some(card::CardFilter::any()),
+            _ => Some(card::parse_card_filter("creature").unwrap())
+        };
+        return Some(card::Effect::AddCounters {
+            counter: card::parse_counter_type(&cap[2])?,
+            count,
+            filter
+        });
+    }
+    return None;
+}
+
+// Effects that happen as the card is cast or enters the battlefield, like
+// 'Opt' or the scry lands.
+fn parse_cast_effects(line: &str, _ctx: &Context) -> Vec<card::Effect> {
+    lazy_static! {
+        static ref SCRY: Regex = Regex::new(r"^Scry (\d+)\.$").unwrap();
+        static ref SCRY_THEN_DRAW: Regex = Regex::new(r"^Scry (\d+), then draw a card\.$").unwrap();
+        static ref SURVEIL: Regex = Regex::new(r"^Surveil (\d+)\.$").unwrap();
+        static ref ETB_SCRY: Regex = Regex::new(r"^When ~ enters, scry (\d+)\.$").unwrap();
+        static ref ETB_SURVEIL: Regex = Regex::new(r"^When ~ enters, surveil (\d+)\.$").unwrap();
+        static ref DRAW_A_CARD: Regex = Regex::new(r"^Draw a card\.$").unwrap();
+        static ref CASCADE: Regex = Regex::new(r"^Cascade((, cascade)*)( \(.*\))?$").unwrap();
+        static ref DISCOVER: Regex = Regex::new(r"^Discover (\d+)\.$").unwrap();
+        static ref ETB_DISCOVER: Regex = Regex::new(r"^When ~ enters, discover (\d+)\.$").unwrap();
+        static ref ETB: Regex = Regex::new(r"^When ~ enters, (.*)$").unwrap();
+    }
+
+    if let Some(cap) = SCRY.captures(line) {
+        return vec![card::Effect::Scry(cap[1].parse().unwrap())];
+    } else if let Some(cap) = SCRY_THEN_DRAW.captures(line) {
+        return vec![card::Effect::Scry(cap[1].parse().unwrap()), card::Effect::Draw(vec![1])];
+    } else if let Some(cap) = SURVEIL.captures(line) {
+        return vec![card::Effect::Surveil(cap[1].parse().unwrap())];
+    } else if let Some(cap) = ETB_SCRY.captures(line) {
+        return vec![card::Effect::Scry(cap[1].parse().unwrap())];
+    } else if let Some(cap) = ETB_SURVEIL.captures(line) {
+        return vec![card::Effect::Surveil(cap[1].parse().unwrap())];
+    } else if DRAW_A_CARD.is_match(line) {
+        return vec![card::Effect::Draw(vec![1])];
+    } else if let Some(cap) = CASCADE.captures(line) {
+        let count = 1 + cap[1].matches("cascade").count();
+        return (0..count).map(|_| card::Effect::Cascade).collect();
+    } else if let Some(cap) = DISCOVER.captures(line) {
+        return vec![card::Effect::Discover(cap[1].parse().unwrap())];
+    } else if let Some(cap) = ETB_DISCOVER.captures(line) {
+        return vec![card::Effect::Discover(cap[1].parse().unwrap())];
+    } else if let Some(effect) = parse_counters_effect(line) {
+        return vec![effect];
+    } else if let Some(cap) = ETB.captures(line) {
+        return parse_counters_effect(&cap[1]).into_iter().collect();
+    }
+
+    return Vec::new();
+}
+
+// Cost reductions, like 'Goblin Electromancer' or affinity. With eminence,
+// like on 'The Ur-Dragon', it also works from the command zone.
+fn parse_static_abilities(line: &str) -> Vec<card::Ability> {
+    lazy_static! {
+        static ref SPELLS_COST_LESS: Regex = Regex::new(r"(?:^|, )(?:[Oo]ther )?([\w ]+?) spells you cast cost \{(\d+)\} less to cast\.$").unwrap();
+        static ref COSTS_LESS_FOR_EACH: Regex = Regex::new(r"^~ costs \{1\} less to cast for each (\w+) you control\.$").unwrap();
+        static ref AFFINITY: Regex = Regex::new(r"^Affinity for (\w+)s\b").unwrap();
+    }
+
+    let ability = |effect: card::Effect, zones: BitFlags<zone::ZoneType>| card::Ability {
+        trigger: card::Trigger::Static,
+        cost: card::Cost::None,
+        effect,
+        availability: 1.0,
+        zones,
+        condition: None
+    };
+    let reduction_for_each = |permanent: &str| {
+        let filter = card::parse_card_filter(permanent).ok()?;
+        return Some(ability(card::Effect::CostReduction {
+            filter: card::CardFilter::any(),
+            amount: card::Amount::Count(filter),
+            floor: 0
+        }, zone::ZoneType::Hand.into()));
+    };
+
+    if let Some(cap) = SPELLS_COST_LESS.captures(line) {
+        let filter = match parse_spell_filter(&cap[1]) {
+            Some(filter) => filter,
+            None => return Vec::new()
+        };
+        let effect = card::Effect::CostReduction {
+            filter,
+            amount: card::Amount::Expr(crate::expr::Expr::Number(cap[2].parse().unwrap())),
+            floor: 0
+        };
+        let mut zones : BitFlags<zone::ZoneType> = zone::ZoneType::Battlefield.into();
+        if line.contains("in the command zone") {
+            zones |= zone::ZoneType::Command;
+        }
+        return vec![ability(effect, zones)];
+    } else if let Some(cap) = COSTS_LESS_FOR_EACH.captures(line) {
+        return reduction_for_each(&cap[1]).into_iter().collect();
+    } else if let Some(cap) = AFFINITY.captures(line) {
+        return reduction_for_each(&cap[1]).into_iter().collect();
+    }
+    return Vec::new();
+}
+
+// Which spells an effect applies to, like "Instant and sorcery" or "Dragon".
+fn parse_spell_filter(text: &str) -> Option<card::CardFilter> {
+    let types = text.replace(" and ", "/").replace(" or ", "/");
+    if let Ok(filter) = card::parse_card_filter(&types) {
+        return Some(filter);
+    }
+    if text.contains(' ') {
+        return None;
+    }
+    let mut filter = card::CardFilter::any();
+    filter.subtype = Some(text.to_lowercase());
+    return Some(filter);
+}
+
+// Cycling and landcycling, like 'Ash Barrens' and the cycling lands. The
+// reminder text is ignored as the keyword says it all.
+fn parse_hand_ability(line: &str) -> Option<card::Ability> {
+    lazy_static! {
+        static ref CYCLING: Regex = Regex::new(r"^(Basic land|Plains|Island|Swamp|Mountain|Forest)?[cC]ycling ((\{\w+\})+)").unwrap();
+    }
+
+    let cap = CYCLING.captures(line)?;
+    let cost = mana::ManaPool::new_from_string(&cap[2]).ok()?;
+    let effect = match cap.get(1) {
+        Some(land_type) => card::Effect::FetchLand {
+            to_hand: vec![land_type.as_str().to_lowercase()],
+            to_battlefield: Vec::new()
+        },
+        None => card::Effect::Draw(vec![1])
+    };
+    return Some(card::Ability {
+        trigger: card::Trigger::Activated,
+        cost: card::Cost::Mana(cost),
+        effect,
+        availability: 1.0,
+        zones: zone::ZoneType::Hand.into(),
+        condition: None
+    });
+}
+
 pub fn parse_additional_cost(ctx: &Context) -> Option<card::AdditionalCost>
 {
     lazy_static! {
-        static ref RETURN_LAND_TO_HAND : Regex = Regex::new(r"^When (.*) enters the battlefield, return a land you control to its owner's hand.$").unwrap();
+        static ref RETURN_LAND_TO_HAND : Regex = Regex::new(r"^When ~ enters, return a land you control to its owner's hand\.$").unwrap();
     }
 
-    for line in ctx.text.split("\n").map(|l| l.trim()) {
-        if let Some(cap) = RETURN_LAND_TO_HAND.captures(line) {
-            if &cap[1] == ctx.card_name {
-                return Some(card::AdditionalCost::ReturnLandToHand);
-            }
+    for line in normalize(ctx).split("\n").map(|l| l.trim()) {
+        if RETURN_LAND_TO_HAND.is_match(line) {
+            return Some(card::AdditionalCost::ReturnLandToHand);
         }
     }
 
     return None;
 }
 
+pub fn parse_enters_tapped(ctx: &Context) -> card::EntersTapped {
+    lazy_static! {
+        static ref TAPPED: Regex = Regex::new(r"^~ enters tapped\.$").unwrap();
+        static ref TAPPED_UNLESS: Regex = Regex::new(r"^~ enters tapped unless you (.*)\.$").unwrap();
+        static ref PAY_LIFE: Regex = Regex::new(r"^As ~ enters, you may pay (\d+) life\. If you don't, it enters tapped\.$").unwrap();
+        static ref CONTROL_TYPES: Regex = Regex::new(r"^control an? (\w+)(?: or an? (\w+))?$").unwrap();
+        static ref OTHER_LANDS: Regex = Regex::new(r"^control (\w+) or (fewer|more) other lands$").unwrap();
+        static ref BASIC_LANDS: Regex = Regex::new(r"^control (\w+) or more basic lands$").unwrap();
+        static ref OPPONENTS: Regex = Regex::new(r"^have (\w+) or more opponents$").unwrap();
+    }
+
+    for line in normalize(ctx).split("\n").map(|l| l.trim()) {
+        if let Some(cap) = PAY_LIFE.captures(line) {
+            return card::EntersTapped::UnlessPayLife(cap[1].parse().unwrap());
+        } else if TAPPED.is_match(line) {
+            return card::EntersTapped::Always;
+        } else if let Some(cap) = TAPPED_UNLESS.captures(line) {
+            let condition = &cap[1];
+            if let Some(types) = CONTROL_TYPES.captures(condition) {
+                let land_types = types.iter().skip(1).flatten().map(|t| t.as_str().to_lowercase()).collect();
+                return card::EntersTapped::UnlessControl(land_types);
+            } else if let Some(lands) = OTHER_LANDS.captures(condition) {
+                if let Some(count) = parse_number(&lands[1]) {
+                    return match &lands[2] {
+                        "fewer" => card::EntersTapped::UnlessAtMostOtherLands(count),
+                        _ => card::EntersTapped::UnlessAtLeastOtherLands(count)
+                    };
+                }
+            } else if let Some(count) = BASIC_LANDS.captures(condition).and_then(|lands| parse_number(&lands[1])) {
+                return card::EntersTapped::UnlessAtLeastBasicLands(count);
+            } else if let Some(count) = OPPONENTS.captures(condition).and_then(|opponents| parse_number(&opponents[1])) {
+                return card::EntersTapped::UnlessOpponents(count);
+            }
+            // A condition we don't understand, assume the worst.
+            return card::EntersTapped::Always;
+        }
+    }
+
+    return card::EntersTapped::Never;
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    match text {
+        "one" => Some(1),
+        "two" => Some(2),
+        "three" => Some(3),
+        "four" => Some(4),
+        "five" => Some(5),
+        "six" => Some(6),
+        "seven" => Some(7),
+        "eight" => Some(8),
+        "nine" => Some(9),
+        "ten" => Some(10),
+        _ => text.parse::<u32>().ok()
+    }
+}
+
+fn parse_graveyard_cast(line: &str) -> Option<card::Ability>
+{
+    lazy_static! {
+        static ref FLASHBACK : Regex = Regex::new(r"^Flashback (\{[^ ]*\})").unwrap();
+        static ref ESCAPE : Regex = Regex::new(r"^Escape—(\{[^ ,]*\}), Exile (\w+) other cards from your graveyard\.").unwrap();
+    }
+
+    let (cost, exile) = if let Some(cap) = FLASHBACK.captures(line) {
+        (mana::ManaPool::new_from_string(&cap[1]).ok()?, 0)
+    } else if let Some(cap) = ESCAPE.captures(line) {
+        (mana::ManaPool::new_from_string(&cap[1]).ok()?, parse_number(&cap[2])?)
+    } else {
+        return None;
+    };
+    return Some(card::Ability {
+        trigger: card::Trigger::Activated,
+        cost: card::Cost::Mana(cost),
+        effect: card::Effect::CastFromGraveyard { exile },
+        availability: 1.0,
+        zones: zone::ZoneType::Graveyard.into(),
+        condition: None
+    });
+}
 
 #[cfg(test)]
 mod tests {
@@ -201,10 +544,29 @@ mod tests {
                 assert_eq!(ability.trigger, card::Trigger::Activated);
                 assert_eq!(ability.cost, card::Cost::TapMana(mana::ManaPool::new_from_single(&mana::COLORLESS)));
                 assert_eq!(ability.effect, card::Effect::ProduceMana(mana::ManaPool::new_from_sequence(&vec![mana::RED, mana::WHITE])));
+                assert!(ability.is_mana_converter());
             }
         }
     }
 
+    #[test]
+    fn test_oracle_parse_mana_converters() {
+        let sunken_ruins_text = "{T}: Add {C}.\n{U/B}, {T}: Add {U}{U}, {U}{B}, or {B}{B}.";
+        let abilities = parse(&Context { text: sunken_ruins_text, card_name: "Sunken Ruins" }).unwrap();
+        assert_eq!(abilities.len(), 2);
+        assert!(!abilities[0].is_mana_converter());
+        assert!(abilities[1].is_mana_converter());
+        let blue_or_black = mana::ManaPool::new_from_string("{U/B}").unwrap();
+        assert_eq!(abilities[1].cost, card::Cost::TapMana(blue_or_black.clone()));
+        assert_eq!(abilities[1].effect, card::Effect::ProduceMana(blue_or_black.multiplied(2)));
+
+        let prismatic_lens_text = "{T}: Add {C}.\n{1}, {T}: Add one mana of any color.";
+        let abilities = parse(&Context { text: prismatic_lens_text, card_name: "Prismatic Lens" }).unwrap();
+        assert_eq!(abilities.len(), 2);
+        assert!(abilities[1].is_mana_converter());
+        assert_eq!(abilities[1].effect, card::Effect::ProduceMana(mana::ManaPool::new_from_single(&mana::ALL)));
+    }
+
     #[test]
     fn test_oracle_parse_commanders_sphere() {
         let commanders_sphere_text = "{T}: Add one mana of any color in your commander's color identity.\nSacrifice Commander's Sphere: Draw a card.";
@@ -243,6 +605,256 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_oracle_parse_horizon_canopy() {
+        let horizon_canopy_text = "{T}, Pay 1 life: Add {G} or {W}.\n{1}, {T}, Sacrifice Horizon Canopy: Draw a card.";
+        match parse(&Context { text: horizon_canopy_text, card_name: "Horizon Canopy" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 2);
+                assert_eq!(abilities[0].cost, card::Cost::TapLife(1));
+                assert_eq!(abilities[0].cost.is_life(), Some(1));
+                assert!(abilities[0].effect.is_produce_mana());
+                assert_eq!(abilities[1].cost.is_life(), None);
+            }
+        }
+        assert_eq!(parse_cost("{T}, Pay 1 life, Sacrifice ~", &Context { text: "", card_name: "Prismatic Vista" }), Ok(Some(card::Cost::TapSacrificeLife(1))));
+        assert_eq!(parse_cost("Pay 2 life", &Context { text: "", card_name: "Bolas's Citadel" }), Ok(Some(card::Cost::Life(2))));
+    }
+
+    #[test]
+    fn test_oracle_parse_garruk_wildspeaker() {
+        let garruk_text = "+1: Untap two target lands.\n−1: Create a 3/3 green Beast creature token.\n−4: Creatures you control get +3/+3 and gain trample until end of turn.";
+        match parse(&Context { text: garruk_text, card_name: "Garruk Wildspeaker" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 2);
+                assert_eq!(abilities[0].trigger, card::Trigger::Activated);
+                assert_eq!(abilities[0].cost, card::Cost::Loyalty(1));
+                assert_eq!(abilities[0].effect, card::Effect::UntapLands(2));
+                assert_eq!(abilities[1].cost, card::Cost::Loyalty(-1));
+                match &abilities[1].effect {
+                    card::Effect::CreateToken { token, count } => {
+                        assert_eq!(*count, 1);
+                        assert_eq!(token.name, "Beast Token");
+                        assert_eq!(token.type_string, "Token Creature — Beast");
+                        assert!(token.types.contains(card::Types::Creature));
+                    },
+                    _ => assert!(false)
+                }
+            }
+        }
+        assert_eq!(parse_cost("0", &Context { text: "", card_name: "Tamiyo" }), Ok(Some(card::Cost::Loyalty(0))));
+    }
+
+    #[test]
+    fn test_oracle_parse_cost_reductions() {
+        let ur_dragon_text = "Eminence — As long as The Ur-Dragon is in the command zone or on the battlefield, other Dragon spells you cast cost {1} less to cast.\nFlying";
+        let abilities = parse(&Context { text: ur_dragon_text, card_name: "The Ur-Dragon" }).unwrap();
+        assert_eq!(abilities.len(), 1);
+        assert_eq!(abilities[0].zones, zone::ZoneType::Battlefield | zone::ZoneType::Command);
+        match &abilities[0].effect {
+            card::Effect::CostReduction { filter, amount: _, floor } => {
+                assert_eq!(filter.subtype, Some("dragon".to_string()));
+                assert_eq!(*floor, 0);
+            },
+            _ => assert!(false)
+        }
+
+        let electromancer_text = "Instant and sorcery spells you cast cost {1} less to cast.";
+        let abilities = parse(&Context { text: electromancer_text, card_name: "Goblin Electromancer" }).unwrap();
+        match &abilities[0].effect {
+            card::Effect::CostReduction { filter, amount: _, floor: _ } => assert_eq!(*filter, card::parse_card_filter("instant/sorcery").unwrap()),
+            _ => assert!(false)
+        }
+
+        let frogmite_text = "Affinity for artifacts (This spell costs {1} less to cast for each artifact you control.)";
+        let abilities = parse(&Context { text: frogmite_text, card_name: "Frogmite" }).unwrap();
+        assert_eq!(abilities.len(), 1);
+        assert_eq!(abilities[0].zones, zone::ZoneType::Hand);
+        assert_eq!(abilities[0].effect, card::Effect::CostReduction {
+            filter: card::CardFilter::any(),
+            amount: card::Amount::Count(card::parse_card_filter("artifact").unwrap()),
+            floor: 0
+        });
+    }
+
+    #[test]
+    fn test_oracle_parse_counters() {
+        let karns_bastion_text = "{T}: Add {C}.\n{4}, {T}: Proliferate. (Choose any number of permanents and/or players, then give each another counter of each kind already there.)";
+        match parse(&Context { text: karns_bastion_text, card_name: "Karn's Bastion" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 2);
+                assert_eq!(abilities[1].cost, card::Cost::TapMana(mana::ManaPool::new_from_string("{4}").unwrap()));
+                assert_eq!(abilities[1].effect, card::Effect::Proliferate);
+            }
+        }
+        let evolution_sage_text = "Whenever a land you control enters, proliferate.";
+        assert!(parse(&Context { text: evolution_sage_text, card_name: "Evolution Sage" }).is_none());
+
+        let pollenbright_druid_text = "When Pollenbright Druid enters the battlefield, choose one —\n• Put a +1/+1 counter on target creature.\n• Proliferate.";
+        assert!(parse(&Context { text: pollenbright_druid_text, card_name: "Pollenbright Druid" }).is_none());
+
+        let ivy_lane_denizen_text = "When Ivy Lane Denizen enters the battlefield, put a +1/+1 counter on target creature.";
+        match parse(&Context { text: ivy_lane_denizen_text, card_name: "Ivy Lane Denizen" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 1);
+                assert_eq!(abilities[0].trigger, card::Trigger::Cast);
+                assert_eq!(abilities[0].effect, card::Effect::AddCounters {
+                    counter: card::CounterType::PlusOne,
+                    count: 1,
+                    filter: Some(card::parse_card_filter("creature").unwrap())
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn test_oracle_parse_scry() {
+        let temple_text = "Temple of Malady enters the battlefield tapped.\nWhen Temple of Malady enters the battlefield, scry 1.\n{T}: Add {B} or {G}.";
+        match parse(&Context { text: temple_text, card_name: "Temple of Malady" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 2);
+                assert_eq!(abilities[1].trigger, card::Trigger::Cast);
+                assert_eq!(abilities[1].cost, card::Cost::None);
+                assert_eq!(abilities[1].effect, card::Effect::Scry(1));
+            }
+        }
+
+        let opt_text = "Scry 1.\nDraw a card.";
+        match parse(&Context { text: opt_text, card_name: "Opt" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 2);
+                assert_eq!(abilities[0].effect, card::Effect::Scry(1));
+                assert_eq!(abilities[1].effect, card::Effect::Draw(vec![1]));
+            }
+        }
+
+        let preordain_text = "Scry 2, then draw a card.";
+        match parse(&Context { text: preordain_text, card_name: "Preordain" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 2);
+                assert_eq!(abilities[0].effect, card::Effect::Scry(2));
+                assert_eq!(abilities[1].effect, card::Effect::Draw(vec![1]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_oracle_parse_cascade() {
+        let bloodbraid_elf_text = "Haste\nCascade (When you cast this spell, exile cards from the top of your library until you exile a nonland card that costs less. You may cast it without paying its mana cost. Put the exiled cards on the bottom of your library in a random order.)";
+        match parse(&Context { text: bloodbraid_elf_text, card_name: "Bloodbraid Elf" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 1);
+                assert_eq!(abilities[0].trigger, card::Trigger::Cast);
+                assert_eq!(abilities[0].effect, card::Effect::Cascade);
+            }
+        }
+
+        let maelstrom_wanderer_text = "Creatures you control have haste.\nCascade, cascade";
+        match parse(&Context { text: maelstrom_wanderer_text, card_name: "Maelstrom Wanderer" }) {
+            None => assert!(false),
+            Some(abilities) => assert_eq!(abilities.iter().filter(|a| a.effect == card::Effect::Cascade).count(), 2)
+        }
+
+        let carnosaur_text = "Trample\nWhen Trumpeting Carnosaur enters the battlefield, discover 5.";
+        match parse(&Context { text: carnosaur_text, card_name: "Trumpeting Carnosaur" }) {
+            None => assert!(false),
+            Some(abilities) => assert_eq!(abilities[0].effect, card::Effect::Discover(5))
+        }
+    }
+
+    #[test]
+    fn test_oracle_parse_cycling() {
+        let ash_barrens_text = "{T}: Add {C}.\nBasic landcycling {1} ({1}, Discard this card: Search your library for a basic land card, reveal it, put it into your hand, then shuffle.)";
+        match parse(&Context { text: ash_barrens_text, card_name: "Ash Barrens" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 2);
+                assert_eq!(abilities[0].zones, zone::ZoneType::Battlefield);
+                assert_eq!(abilities[1].zones, zone::ZoneType::Hand);
+                assert_eq!(abilities[1].trigger, card::Trigger::Activated);
+                assert_eq!(abilities[1].cost, card::Cost::Mana(mana::ManaPool::new_from_sequence(&vec![mana::COLORLESS])));
+                assert_eq!(abilities[1].effect, card::Effect::FetchLand { to_hand: vec!["basic land".to_string()], to_battlefield: Vec::new() });
+            }
+        }
+
+        let fetid_pools_text = "({T}: Add {U} or {B}.)\nFetid Pools enters the battlefield tapped.\nCycling {2} ({2}, Discard this card: Draw a card.)";
+        match parse(&Context { text: fetid_pools_text, card_name: "Fetid Pools" }) {
+            None => assert!(false),
+            Some(abilities) => {
+                assert_eq!(abilities.len(), 1);
+                assert_eq!(abilities[0].zones, zone::ZoneType::Hand);
+                assert_eq!(abilities[0].cost, card::Cost::Mana(mana::ManaPool::new_from_sequence(&vec![mana::COLORLESS, mana::COLORLESS])));
+                assert_eq!(abilities[0].effect, card::Effect::Draw(vec![1]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_oracle_parse_enters_tapped() {
+        let cases = [
+            ("Temple of Malady", "Temple of Malady enters the battlefield tapped.\nWhen Temple of Malady enters the battlefield, scry 1.", card::EntersTapped::Always),
+            ("Hinterland Harbor", "Hinterland Harbor enters the battlefield tapped unless you control a Forest or an Island.\n{T}: Add {G} or {U}.",
+                card::EntersTapped::UnlessControl(vec!["forest".to_string(), "island".to_string()])),
+            ("Blooming Marsh", "Blooming Marsh enters the battlefield tapped unless you control two or fewer other lands.", card::EntersTapped::UnlessAtMostOtherLands(2)),
+            ("Shipwreck Marsh", "Shipwreck Marsh enters the battlefield tapped unless you control two or more other lands.", card::EntersTapped::UnlessAtLeastOtherLands(2)),
+            ("Prairie Stream", "({T}: Add {W} or {U}.)\nPrairie Stream enters the battlefield tapped unless you control two or more basic lands.", card::EntersTapped::UnlessAtLeastBasicLands(2)),
+            ("Overgrown Tomb", "({T}: Add {B} or {G}.)\nAs Overgrown Tomb enters the battlefield, you may pay 2 life. If you don't, it enters the battlefield tapped.", card::EntersTapped::UnlessPayLife(2)),
+            ("Bountiful Promenade", "Bountiful Promenade enters the battlefield tapped unless you have two or more opponents.", card::EntersTapped::UnlessOpponents(2)),
+            ("Minas Tirith", "Minas Tirith enters the battlefield tapped unless you control a legendary creature.", card::EntersTapped::Always),
+            ("Command Tower", "{T}: Add one mana of any color in your commander's color identity.", card::EntersTapped::Never),
+        ];
+        for (name, text, expected) in cases {
+            assert_eq!(parse_enters_tapped(&Context { text, card_name: name }), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_oracle_normalize() {
+        let cases = [
+            ("Temple of Malady", "Temple of Malady enters the battlefield tapped.", "~ enters tapped."),
+            ("Temple of Malady", "This land enters tapped.\nWhen this land enters, scry 1.", "~ enters tapped.\nWhen ~ enters, scry 1."),
+            ("Grakmaw, Skyclave Ravager", "Grakmaw enters with three +1/+1 counters on it.", "~ enters with three +1/+1 counters on it."),
+            ("Commander's Sphere", "Sacrifice this artifact: Draw a card.", "Sacrifice ~: Draw a card."),
+        ];
+        for (name, text, expected) in cases {
+            assert_eq!(normalize(&Context { text, card_name: name }), expected);
+        }
+
+        let temple_text = "This land enters tapped.\nWhen this land enters, scry 1.\n{T}: Add {B} or {G}.";
+        assert_eq!(parse_enters_tapped(&Context { text: temple_text, card_name: "Temple of Malady" }), card::EntersTapped::Always);
+        match parse(&Context { text: temple_text, card_name: "Temple of Malady" }) {
+            None => assert!(false),
+            Some(abilities) => assert_eq!(abilities[1].effect, card::Effect::Scry(1))
+        }
+
+        let karoo_text = "This land enters tapped.\nWhen this land enters, return a land you control to its owner's hand.\n{T}: Add {R}{W}.";
+        assert_eq!(parse_additional_cost(&Context { text: karoo_text, card_name: "Boros Garrison" }), Some(card::AdditionalCost::ReturnLandToHand));
+    }
+
+    #[test]
+    fn test_oracle_parse_graveyard_cast() {
+        assert_eq!(parse_graveyard_cast("Flashback—{1}{U}, Pay 3 life."), None);
+
+        let think_twice_text = "Draw a card.\nFlashback {2}{U} (You may cast this card from your graveyard for its flashback cost. Then exile it.)";
+        let abilities = parse(&Context { text: think_twice_text, card_name: "Think Twice" }).unwrap();
+        assert_eq!(abilities.len(), 2);
+        assert_eq!(abilities[1].zones, zone::ZoneType::Graveyard);
+        assert_eq!(abilities[1].cost, card::Cost::Mana(mana::ManaPool::new_from_sequence(&vec![mana::COLORLESS, mana::COLORLESS, mana::BLUE])));
+        assert_eq!(abilities[1].effect, card::Effect::CastFromGraveyard { exile: 0 });
+
+        let escape = parse_graveyard_cast("Escape—{3}{G}{G}, Exile five other cards from your graveyard.").unwrap();
+        assert_eq!(escape.cost, card::Cost::Mana(mana::ManaPool::new_from_sequence(&vec![mana::COLORLESS, mana::COLORLESS, mana::COLORLESS, mana::GREEN, mana::GREEN])));
+        assert_eq!(escape.effect, card::Effect::CastFromGraveyard { exile: 5 });
+    }
+
     #[test]
     fn test_oracle_parse_arcane_sanctum() {
         let arcane_sanctum_text = "{T}: Add {W}, {U}, or {B}.";
@@ -11,17 +11,54 @@ fn show_commander_stats(stats: &Vec<game::GameStats>, _settings: &game::Settings
 
     println!("Commander arrives on turn ........: {:.1} (avg)", commander_average_turn);
     println!("games Commander didn't arrive ....: {:.2}% ({})", 100.0 * not_played as f32 / stats.len() as f32, not_played);
+    println!("Commander casts / game ...........: {:.2}", average(stats.iter().map(|s| s.commander_casts).sum(), stats.len()));
 }
 
 fn show_draw_stats(stats: &Vec<game::GameStats>, settings: &game::Settings) {
     let total_turn_count = stats.len() * (settings.turn_count as usize);
-    let draw_curve = average(stats.iter().map(|s| s.turns_stats.iter()).flatten().map(|s| s.cards_drawn).sum(), total_turn_count);
+    let draw_curve = average(stats.iter().flat_map(|s| s.turns_stats.iter()).map(|s| s.cards_drawn).sum(), total_turn_count);
     println!("cards/round average ..............: {:.2}", draw_curve);
 
+    let discards = average(stats.iter().flat_map(|s| s.turns_stats.iter()).map(|s| s.cards_discarded).sum(), stats.len());
+    println!("cards discarded / game ...........: {:.2}", discards);
+
+    let free_casts = average(stats.iter().flat_map(|s| s.turns_stats.iter()).map(|s| s.cards_cast_free).sum(), stats.len());
+    println!("cards cast for free / game .......: {:.2}", free_casts);
+
+    let life_paid = average(stats.iter().flat_map(|s| s.turns_stats.iter()).map(|s| s.life_paid).sum(), stats.len());
+    println!("life paid / game .................: {:.2}", life_paid);
+
+    let removed = average(stats.iter().flat_map(|s| s.turns_stats.iter()).map(|s| s.permanents_removed).sum(), stats.len());
+    println!("permanents removed / game ........: {:.2}", removed);
+
+    let countered = average(stats.iter().flat_map(|s| s.turns_stats.iter()).map(|s| s.spells_countered).sum(), stats.len());
+    println!("spells countered / game ..........: {:.2}", countered);
+
     let out_of_cards = stats.iter().filter(|s| s.out_of_cards).count();
     println!("games library ran out of cards ...: {:.2}% ({})", 100.0 * out_of_cards as f32 / stats.len() as f32, out_of_cards);
 }
 
+fn show_draw_engine_stats(stats: &[game::GameStats], _settings: &game::Settings) {
+    let mut drawn_by: std::collections::HashMap<&str, u32> = std::collections::HashMap::new();
+    for turn_stats in stats.iter().flat_map(|s| s.turns_stats.iter()) {
+        for (name, count) in &turn_stats.cards_drawn_by {
+            *drawn_by.entry(name.as_str()).or_insert(0) += count;
+        }
+    }
+    if drawn_by.is_empty() {
+        return;
+    }
+
+    let mut engines: Vec<(&str, u32)> = drawn_by.into_iter().collect();
+    engines.sort_by(|(name_a, count_a), (name_b, count_b)| count_b.cmp(count_a).then(name_a.cmp(name_b)));
+
+    println!();
+    println!("Draw engines (cards / game):");
+    for (name, count) in engines {
+        println!(" - {:.<40}: {:.2}", format!("{} ", name), average(count, stats.len()));
+    }
+}
+
 fn show_ram_stats(stats: &Vec<game::GameStats>, settings: &game::Settings) {
 
     let ramp_curve: f32 = stats
@@ -73,6 +110,7 @@ pub fn show_statistics(stats: &Vec<game::GameStats>, settings: &game::Settings)
     show_commander_stats(stats, settings);
     show_draw_stats(stats, settings);
     show_ram_stats(stats, settings);
+    show_draw_engine_stats(stats, settings);
 
     println!();
     println!("games simulated ..................: {}", stats.len());
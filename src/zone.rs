@@ -1,5 +1,6 @@
 use crate::card::{Card, Types};
 use crate::mana;
+use enumflags2::bitflags;
 use rand::distributions::{Distribution, Uniform};
 
 #[derive(Debug, Clone)]
@@ -8,6 +9,20 @@ pub struct Zone<'db> {
     pub cards: Vec<Card<'db>>
 }
 
+// The kinds of zones in the game, used to declare which zones an ability
+// works from.
+#[bitflags]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoneType {
+    Library = 0x01,
+    Hand = 0x02,
+    Battlefield = 0x04,
+    Graveyard = 0x08,
+    Exile = 0x10,
+    Command = 0x20,
+}
+
 #[derive(Debug, Clone)]
 pub struct PipCounts {
     pub black: f32,
@@ -65,10 +80,28 @@ impl<'db> Zone<'db> {
         self.cards.iter_mut().for_each(|c| c.tapped = false);
     }
 
+    // The top of the zone is the end of 'cards', which is where draw() takes
+    // cards from.
     pub fn draw(&mut self) -> Option<Card<'db>> {
         return self.cards.pop();
     }
 
+    pub fn put_on_top(&mut self, card : Card<'db>) {
+        self.cards.push(card);
+    }
+
+    pub fn put_on_bottom(&mut self, card : Card<'db>) {
+        self.cards.insert(0, card);
+    }
+
+    // Removes up to 'count' cards from the top, the top card comes first.
+    pub fn take_top(&mut self, count : u32) -> Vec<Card<'db>> {
+        let split = self.cards.len().saturating_sub(count as usize);
+        let mut cards = self.cards.split_off(split);
+        cards.reverse();
+        return cards;
+    }
+
     pub fn take(&mut self, id : u32) -> Option<Card<'db>> {
         let mut i = 0;
         while i < self.cards.len() {
@@ -95,15 +128,17 @@ impl<'db> Zone<'db> {
         }).collect();
     }
 
-    pub fn take_land(&mut self, type_string: &str, desired_color: &Option<mana::Color>) -> Option<Card<'db>> {
-        let lower_cased_type_string = type_string.to_lowercase();
+    // Takes a land matching 'type_query', see CardData::matches_type_query(),
+    // preferring one producing 'desired_color'.
+    pub fn take_land(&mut self, type_query: &str, desired_color: &Option<mana::Color>) -> Option<Card<'db>> {
         let mut index: Option<usize> = None;
         if let Some(color) = desired_color {
             index = self.cards
                 .iter()
                 .map(|card| card.data)
                 .position(|d|
-                    d.type_string.to_lowercase().contains(&lower_cased_type_string)
+                    d.types.contains(Types::Land)
+                    && d.matches_type_query(type_query)
                     && d.produced_mana.as_ref().map_or(false, |mana| mana.contains(*color))
                 );
         }
@@ -111,7 +146,7 @@ impl<'db> Zone<'db> {
             index = self.cards
                 .iter()
                 .map(|card| card.data)
-                .position(|d| d.type_string.to_lowercase().contains(&lower_cased_type_string));
+                .position(|d| d.types.contains(Types::Land) && d.matches_type_query(type_query));
         }
 
         return index.map(|i| self.cards.remove(i));
@@ -136,6 +171,31 @@ impl<'db> Zone<'db> {
     }
 }
 
+pub fn parse_zone_type(text : &str) -> Option<ZoneType> {
+    match text {
+        "library" => Some(ZoneType::Library),
+        "hand" => Some(ZoneType::Hand),
+        "battlefield" => Some(ZoneType::Battlefield),
+        "graveyard" => Some(ZoneType::Graveyard),
+        "exile" => Some(ZoneType::Exile),
+        "command" => Some(ZoneType::Command),
+        _ => None
+    }
+}
+
+impl std::fmt::Display for ZoneType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ZoneType::Library => write!(f, "library"),
+            ZoneType::Hand => write!(f, "hand"),
+            ZoneType::Battlefield => write!(f, "battlefield"),
+            ZoneType::Graveyard => write!(f, "graveyard"),
+            ZoneType::Exile => write!(f, "exile"),
+            ZoneType::Command => write!(f, "command"),
+        }
+    }
+}
+
 impl PipCounts {
     pub fn new() -> Self {
         return PipCounts { black: 0.0, blue: 0.0, green: 0.0, red: 0.0, white: 0.0 };
@@ -221,6 +281,44 @@ impl PipCounts {
 mod tests {
 
     use super::*;
+    use crate::card::CardData;
+
+    #[test]
+    fn test_zone_top_and_bottom() {
+        let plains_data = CardData::make_plains_data();
+        let swamp_data = CardData::make_swamp_data();
+        let elk_data = CardData::make_elk_data();
+
+        let mut library = Zone::new("Library");
+        library.add(Card::new_with_id(1, &plains_data));
+        library.add(Card::new_with_id(2, &swamp_data));
+        library.put_on_top(Card::new_with_id(3, &elk_data));
+        library.put_on_bottom(Card::new_with_id(4, &elk_data));
+
+        let top = library.take_top(2);
+        assert_eq!(top.iter().map(|c| c.id).collect::<Vec<u32>>(), vec![3, 2]);
+        assert_eq!(library.draw().map(|c| c.id), Some(1));
+        assert_eq!(library.take_top(5).iter().map(|c| c.id).collect::<Vec<u32>>(), vec![4]);
+        assert_eq!(library.size(), 0);
+    }
+
+    #[test]
+    fn test_zone_take_land() {
+        let plains_data = CardData::make_plains_data();
+        let dual_data = CardData::make_card_data("Savannah", "Land — Forest Plains", "");
+        let elk_data = CardData::make_elk_data();
+
+        let mut library = Zone::new("Library");
+        library.add(Card::new_with_id(1, &elk_data));
+        library.add(Card::new_with_id(2, &plains_data));
+        library.add(Card::new_with_id(3, &dual_data));
+
+        assert_eq!(library.take_land("creature", &None).map(|c| c.id), None);
+        assert_eq!(library.take_land("forest plains", &None).map(|c| c.id), Some(3));
+        assert_eq!(library.take_land("forest/plains", &None).map(|c| c.id), Some(2));
+        assert_eq!(library.take_land("basic land", &None).map(|c| c.id), None);
+        assert_eq!(library.size(), 1);
+    }
 
     #[test]
     fn test_pipcount_prioritized_delta() {